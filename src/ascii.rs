@@ -12,6 +12,10 @@ pub struct AsciiText;
 #[derive(Component)]
 pub struct NineSlice;
 
+/// Cursor in front of the selected line of a list menu
+#[derive(Component)]
+pub struct ListMenuCursor;
+
 #[derive(Copy, Clone)]
 pub struct NineSliceIndices {
     center: usize,
@@ -148,14 +152,84 @@ pub fn spawn_nine_slice(
         .id()
 }
 
+/// Size in tiles of a list menu: border, cursor, text and some padding
+pub fn list_menu_size(lines: &[(String, Color)]) -> Vec2 {
+    let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0) + 4;
+    Vec2::new(width as f32, (lines.len() + 2) as f32)
+}
+
+/// Y offset of a list menu row from the center of the menu
+pub fn list_menu_row_y(line_count: usize, row: usize) -> f32 {
+    let height = (line_count + 2) as f32;
+    (height / 2.0 - 1.5 - row as f32) * TILE_SIZE
+}
+
+/// Boxed list of lines with a cursor in front of the selected one
+pub fn spawn_list_menu(
+    commands: &mut Commands,
+    ascii: &AsciiSheet,
+    indices: &NineSliceIndices,
+    lines: &[(String, Color)],
+    selected: usize,
+    center: Vec3,
+) -> Entity {
+    let size = list_menu_size(lines);
+    let nine_slice = spawn_nine_slice(commands, ascii, indices, size.x, size.y);
+    let mut children = vec![nine_slice];
+    for (i, (line, color)) in lines.iter().enumerate() {
+        children.push(spawn_colored_ascii_text(
+            commands,
+            ascii,
+            line,
+            Vec3::new(
+                (-size.x / 2.0 + 2.5) * TILE_SIZE,
+                list_menu_row_y(lines.len(), i),
+                1.0,
+            ),
+            *color,
+        ));
+    }
+
+    let cursor = spawn_ascii_sprite(
+        commands,
+        ascii,
+        16,
+        Color::RED,
+        Vec3::new(
+            (-size.x / 2.0 + 1.5) * TILE_SIZE,
+            list_menu_row_y(lines.len(), selected),
+            1.0,
+        ),
+        Vec3::splat(1.0),
+    );
+    commands.entity(cursor).insert(ListMenuCursor);
+    children.push(cursor);
+
+    commands
+        .spawn()
+        .insert(Transform::from_translation(center))
+        .insert(GlobalTransform::default())
+        .push_children(&children)
+        .id()
+}
+
 pub fn spawn_ascii_text(
     commands: &mut Commands,
     ascii: &AsciiSheet,
     to_print: &str,
     left_center: Vec3,
+) -> Entity {
+    spawn_colored_ascii_text(commands, ascii, to_print, left_center, Color::rgb(0.8, 0.8, 0.8))
+}
+
+pub fn spawn_colored_ascii_text(
+    commands: &mut Commands,
+    ascii: &AsciiSheet,
+    to_print: &str,
+    left_center: Vec3,
+    color: Color,
 ) -> Entity {
     let mut character_sprites = Vec::new();
-    let color = Color::rgb(0.8, 0.8, 0.8);
     for (i, char) in to_print.chars().enumerate() {
         assert!(char as usize <= 255);
         character_sprites.push(spawn_ascii_sprite(
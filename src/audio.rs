@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
 
-use crate::combat::{CombatState, FightEvent};
+use crate::combat::{CombatState, FightEvent, HitOutcome};
 use crate::GameState;
 
 pub struct GameAudioPlugin;
@@ -27,6 +27,7 @@ impl Plugin for GameAudioPlugin {
             .add_system_set(
                 SystemSet::on_resume(GameState::Overworld).with_system(resume_bgm_music),
             )
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(stop_combat_music))
             .add_system_set(SystemSet::on_enter(CombatState::Reward).with_system(play_reward_sfx))
             .add_system(play_hit_sfx)
             .add_system(volume_control)
@@ -43,7 +44,8 @@ fn play_hit_sfx(
     audio_state: Res<AudioState>,
     mut fight_event: EventReader<FightEvent>,
 ) {
-    if fight_event.iter().count() > 0 {
+    // Misses whiff silently
+    if fight_event.iter().any(|event| event.outcome != HitOutcome::Miss) {
         audio.play_in_channel(audio_state.hit_handle.clone(), &audio_state.sfx_channel);
     }
 }
@@ -53,6 +55,10 @@ fn resume_bgm_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
     audio.resume_channel(&audio_state.bgm_channel);
 }
 
+fn stop_combat_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
+    audio.stop_channel(&audio_state.combat_channel);
+}
+
 fn start_combat_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
     audio.pause_channel(&audio_state.bgm_channel);
     audio.play_looped_in_channel(
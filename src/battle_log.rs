@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_nine_slice, AsciiSheet, NineSliceIndices},
+    GameState, RESOLUTION, TILE_SIZE,
+};
+
+pub const LOG_LINES: usize = 4;
+// Inner width of the box in characters
+pub const LOG_WIDTH: usize = 30;
+// How the player is called in log messages
+pub const PLAYER_NAME: &str = "You";
+
+pub struct BattleLogPlugin;
+
+/// One message for the battle log, long ones get wrapped
+pub struct CombatLogEvent(pub String);
+
+#[derive(Default)]
+pub struct BattleLog {
+    lines: VecDeque<String>,
+}
+
+#[derive(Component)]
+pub struct BattleLogPanel;
+
+#[derive(Component)]
+pub struct BattleLogText;
+
+impl Plugin for BattleLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CombatLogEvent>()
+            .init_resource::<BattleLog>()
+            .add_system_set(SystemSet::on_enter(GameState::Combat).with_system(spawn_battle_log))
+            .add_system_set(SystemSet::on_update(GameState::Combat).with_system(update_battle_log))
+            .add_system_set(SystemSet::on_exit(GameState::Combat).with_system(despawn_battle_log));
+    }
+}
+
+/// "You attack" for the player, "Bat attacks" for everyone else
+pub fn conjugate(subject: &str, verb: &str) -> String {
+    if subject == PLAYER_NAME {
+        format!("{} {}", subject, verb)
+    } else {
+        format!("{} {}s", subject, verb)
+    }
+}
+
+impl BattleLog {
+    fn push(&mut self, message: &str) {
+        for line in wrap(message, LOG_WIDTH) {
+            self.lines.push_back(line);
+        }
+        // Older messages scroll off the top
+        while self.lines.len() > LOG_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+fn wrap(message: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in message.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn spawn_battle_log(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    nine_slice_indices: Res<NineSliceIndices>,
+    mut battle_log: ResMut<BattleLog>,
+) {
+    battle_log.lines.clear();
+
+    let width = (LOG_WIDTH + 2) as f32;
+    let height = (LOG_LINES + 2) as f32;
+    // Above the player's stats, clear of the enemies
+    let center = Vec3::new(
+        -RESOLUTION + width * TILE_SIZE / 2.0,
+        -1.0 + 3.0 * TILE_SIZE + height * TILE_SIZE / 2.0,
+        100.0,
+    );
+
+    let nine_slice = spawn_nine_slice(&mut commands, &ascii, &nine_slice_indices, width, height);
+    commands
+        .spawn()
+        .insert(Transform::from_translation(center))
+        .insert(GlobalTransform::default())
+        .insert(Name::new("Battle Log"))
+        .insert(BattleLogPanel)
+        .add_child(nine_slice);
+}
+
+fn update_battle_log(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    mut battle_log: ResMut<BattleLog>,
+    mut log_events: EventReader<CombatLogEvent>,
+    panel_query: Query<Entity, With<BattleLogPanel>>,
+    text_query: Query<Entity, With<BattleLogText>>,
+) {
+    let mut changed = false;
+    for event in log_events.iter() {
+        battle_log.push(&event.0);
+        changed = true;
+    }
+    if !changed {
+        return;
+    }
+    let panel = match panel_query.get_single() {
+        Ok(panel) => panel,
+        Err(_) => return,
+    };
+
+    for text in text_query.iter() {
+        commands.entity(text).despawn_recursive();
+    }
+    let height = (LOG_LINES + 2) as f32;
+    for (i, line) in battle_log.lines.iter().enumerate() {
+        let text = spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            line,
+            Vec3::new(
+                (-(LOG_WIDTH as f32) / 2.0 + 0.5) * TILE_SIZE,
+                (height / 2.0 - 1.5 - i as f32) * TILE_SIZE,
+                1.0,
+            ),
+        );
+        commands.entity(text).insert(BattleLogText);
+        commands.entity(panel).add_child(text);
+    }
+}
+
+fn despawn_battle_log(mut commands: Commands, panel_query: Query<Entity, With<BattleLogPanel>>) {
+    for panel in panel_query.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
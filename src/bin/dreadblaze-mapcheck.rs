@@ -0,0 +1,311 @@
+//! Checks map files with the parser the game uses, exits with 1 if any map has errors.
+//!
+//! Usage: dreadblaze-mapcheck [--assets DIR] [MAP OR FOLDER]...
+//! Warp targets and enemy names are looked up in the assets folder, "assets" by default.
+//! Without any maps given, everything in assets/maps is checked.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::Context;
+use dreadblaze::map_format::{parse_map_file, LegendErrors, MapData};
+use serde::Deserialize;
+
+const ENEMIES_FILE: &str = "data/game.enemies.ron";
+
+// Only the names matter here, the rest of each definition is skipped
+#[derive(Deserialize)]
+struct EnemyNames {
+    enemies: Vec<EnemyName>,
+}
+
+#[derive(Deserialize)]
+struct EnemyName {
+    name: String,
+}
+
+fn load(path: &Path) -> anyhow::Result<MapData> {
+    let text = fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    parse_map_file(path, &text)
+}
+
+fn load_enemy_names(assets: &Path) -> anyhow::Result<Vec<String>> {
+    let path = assets.join(ENEMIES_FILE);
+    let text = fs::read_to_string(&path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    let list: EnemyNames =
+        ron::de::from_str(&text).with_context(|| format!("Couldn't load {}", path.display()))?;
+    Ok(list.enemies.into_iter().map(|enemy| enemy.name).collect())
+}
+
+fn is_map_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "map" || extension == "tmj")
+}
+
+fn collect_maps(path: &Path, maps: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)
+            .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        entries.sort();
+        for entry in entries {
+            if entry.is_dir() || is_map_file(&entry) {
+                collect_maps(&entry, maps);
+            }
+        }
+    } else {
+        maps.push(path.to_path_buf());
+    }
+}
+
+/// Tiles that block the player, NPCs stand in the way too
+fn blocked(map: &MapData) -> HashSet<(usize, usize)> {
+    let mut blocked: HashSet<(usize, usize)> = map
+        .rows
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, tile)| tile.solid)
+                .map(move |(x, _)| (x, y))
+        })
+        .collect();
+    blocked.extend(map.npcs.iter().map(|(x, y, _)| (*x, *y)));
+    blocked
+}
+
+fn walkable_neighbours(map: &MapData, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+    let mut neighbours = vec![(x + 1, y), (x, y + 1)];
+    if x > 0 {
+        neighbours.push((x - 1, y));
+    }
+    if y > 0 {
+        neighbours.push((x, y - 1));
+    }
+    neighbours
+        .into_iter()
+        .filter(|&(x, y)| map.rows.get(y).is_some_and(|row| x < row.len()))
+        .collect()
+}
+
+fn check_spawn(map: &MapData, errors: &mut Vec<String>) {
+    match map.spawns.as_slice() {
+        [] => errors.push("No player spawn".to_string()),
+        [(x, y)] => {
+            if blocked(map).contains(&(*x, *y)) {
+                errors.push(format!("The spawn at {} is on a solid tile", map.describe_tile(*x, *y)));
+            }
+        }
+        spawns => errors.push(format!(
+            "{} player spawns, at {}",
+            spawns.len(),
+            spawns
+                .iter()
+                .map(|(x, y)| map.describe_tile(*x, *y))
+                .collect::<Vec<_>>()
+                .join("; ")
+        )),
+    }
+}
+
+fn check_rows(map: &MapData, errors: &mut Vec<String>) {
+    let width = map.rows[0].len();
+    for (y, row) in map.rows.iter().enumerate() {
+        if row.len() != width {
+            errors.push(format!(
+                "Line {} is {} tiles wide, the first row of tiles is {}",
+                map.first_line + y,
+                row.len(),
+                width
+            ));
+        }
+    }
+}
+
+// The player walks off the map anywhere the border isn't a wall or a door
+fn check_edges(map: &MapData, errors: &mut Vec<String>) {
+    let warps: HashSet<(usize, usize)> = map.warps.iter().map(|(x, y, _)| (*x, *y)).collect();
+    let last_row = map.rows.len() - 1;
+    let mut open = Vec::new();
+    for (y, row) in map.rows.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            let edge = y == 0 || y == last_row || x == 0 || x == row.len() - 1;
+            if edge && !tile.solid && !warps.contains(&(x, y)) {
+                open.push(map.describe_tile(x, y));
+            }
+        }
+    }
+    if !open.is_empty() {
+        errors.push(format!("The edge is open at {}", open.join("; ")));
+    }
+}
+
+fn check_warps(
+    map: &MapData,
+    assets: &Path,
+    destinations: &mut HashMap<String, Option<MapData>>,
+    errors: &mut Vec<String>,
+) {
+    for (x, y, warp) in map.warps.iter() {
+        let destination = destinations
+            .entry(warp.map.clone())
+            .or_insert_with(|| load(&assets.join(&warp.map)).ok());
+        let destination = match destination {
+            Some(destination) => destination,
+            None => {
+                let reason = if assets.join(&warp.map).exists() {
+                    "which doesn't load"
+                } else {
+                    "which doesn't exist"
+                };
+                errors.push(format!(
+                    "The warp at {} leads to {} {}",
+                    map.describe_tile(*x, *y),
+                    warp.map,
+                    reason
+                ));
+                continue;
+            }
+        };
+        let landing = destination.rows.get(warp.y).and_then(|row| row.get(warp.x));
+        if landing.is_none() || blocked(destination).contains(&(warp.x, warp.y)) {
+            errors.push(format!(
+                "The warp at {} lands on {} in {}, which can't be stood on",
+                map.describe_tile(*x, *y),
+                destination.describe_tile(warp.x, warp.y),
+                warp.map
+            ));
+        }
+    }
+}
+
+fn check_enemies(map: &MapData, enemy_names: &[String], errors: &mut Vec<String>) {
+    for name in map.encounters.enemies.iter() {
+        if !enemy_names.contains(name) {
+            errors.push(format!("Enemy '{}' isn't defined in {}", name, ENEMIES_FILE));
+        }
+    }
+}
+
+// Encounter tiles are only worth something if the player can walk into them
+fn check_grass(map: &MapData, errors: &mut Vec<String>) {
+    let spawn = match map.spawn() {
+        Some(spawn) => spawn,
+        None => return,
+    };
+    let blocked = blocked(map);
+    let flood = |start: (usize, usize), include: &dyn Fn((usize, usize)) -> bool| {
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(tile) = queue.pop_front() {
+            for next in walkable_neighbours(map, tile) {
+                if include(next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    };
+
+    let reachable = flood(spawn, &|tile| !blocked.contains(&tile));
+    let is_grass = |(x, y): (usize, usize)| map.rows[y][x].encounter;
+    let mut unreachable: Vec<(usize, usize)> = map
+        .rows
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| (0..row.len()).map(move |x| (x, y)))
+        .filter(|&tile| is_grass(tile) && !blocked.contains(&tile) && !reachable.contains(&tile))
+        .collect();
+
+    // One error per patch of grass instead of one per tile
+    while let Some(&start) = unreachable.first() {
+        let patch = flood(start, &|tile| is_grass(tile) && !blocked.contains(&tile));
+        unreachable.retain(|tile| !patch.contains(tile));
+        errors.push(format!(
+            "A patch of {} grass tiles at {} can't be reached from the spawn",
+            patch.len(),
+            map.describe_tile(start.0, start.1)
+        ));
+    }
+}
+
+fn main() -> ExitCode {
+    let mut assets = PathBuf::from("assets");
+    let mut inputs = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => match args.next() {
+                Some(dir) => assets = PathBuf::from(dir),
+                None => {
+                    eprintln!("--assets needs a folder");
+                    return ExitCode::from(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("Usage: dreadblaze-mapcheck [--assets DIR] [MAP OR FOLDER]...");
+                return ExitCode::SUCCESS;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() {
+        inputs.push(assets.join("maps"));
+    }
+
+    let mut maps = Vec::new();
+    for input in inputs.iter() {
+        collect_maps(input, &mut maps);
+    }
+
+    let enemy_names = match load_enemy_names(&assets) {
+        Ok(names) => Some(names),
+        Err(err) => {
+            eprintln!("Enemy names aren't checked: {:#}", err);
+            None
+        }
+    };
+
+    let mut destinations = HashMap::new();
+    let mut error_count = 0;
+    for path in maps.iter() {
+        let mut errors = Vec::new();
+        match load(path) {
+            Ok(map) => {
+                check_spawn(&map, &mut errors);
+                check_rows(&map, &mut errors);
+                check_edges(&map, &mut errors);
+                check_warps(&map, &assets, &mut destinations, &mut errors);
+                check_grass(&map, &mut errors);
+                if let Some(enemy_names) = &enemy_names {
+                    check_enemies(&map, enemy_names, &mut errors);
+                }
+            }
+            // Every character missing from the legend gets its own line
+            Err(err) => match err.downcast_ref::<LegendErrors>() {
+                Some(legend_errors) => errors.extend(legend_errors.0.iter().cloned()),
+                None => errors.push(format!("{:#}", err)),
+            },
+        }
+
+        if errors.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for error in errors.iter() {
+            println!("{}: {}", path.display(), error);
+        }
+        error_count += errors.len();
+    }
+
+    println!("Checked {} maps, found {} errors", maps.len(), error_count);
+    if error_count > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
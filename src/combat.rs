@@ -1,28 +1,52 @@
+use std::collections::{HashSet, VecDeque};
+
 use bevy::{prelude::*, render::camera::Camera2d};
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    battle_log::{conjugate, CombatLogEvent, PLAYER_NAME},
     ascii::{
-        spawn_ascii_sprite, spawn_ascii_text, spawn_nine_slice, AsciiSheet, NineSlice,
-        NineSliceIndices,
+        list_menu_row_y, list_menu_size, spawn_ascii_sprite, spawn_ascii_text, spawn_list_menu,
+        spawn_nine_slice, AsciiSheet, ListMenuCursor, NineSlice, NineSliceIndices,
     },
-    fadeout::create_fadeout,
+    enemies::{EnemyDefinition, EnemyDefinitions, EnemyList},
+    enemy_ai::EnemyAction,
+    equipment::{BaseStats, Equipment, StatBlock},
+    fadeout::{create_fadeout, create_transition_fadeout, FadeTransition},
     graphics::{spawn_enemy_sprite, CharacterSheet, VfxSheet},
-    player::{Player, self},
+    items::{Inventory, ItemDefinitions, ItemList},
+    player::Player,
+    progression::{Progression, ProgressionDefinition},
+    rng::GameRng,
+    spells::{Spell, SpellDefinitions, SpellList, SpellTarget, Spellbook},
+    status::{StatusEffects, StatusKind},
+    tilemap::CurrentArea,
     GameState, RESOLUTION, TILE_SIZE,
 };
 
 #[derive(Component)]
 pub struct Enemy {
-    enemy_type: EnemyType,
+    definition: EnemyDefinition,
 }
 
-pub const MENU_COUNT: isize = 3;
+pub const MENU_COUNT: isize = 5;
+pub const MAX_ENEMIES: usize = 4;
+pub const ENEMY_SPACING: f32 = 0.8;
+pub const TIMELINE_LENGTH: usize = 6;
+pub const BURN_CHANCE: f32 = 0.3;
+pub const ENEMY_FLEE_CHANCE: f32 = 0.5;
+pub const BASE_ESCAPE_CHANCE: f32 = 0.5;
+// Escape chance gained per point of speed over the fastest enemy
+pub const ESCAPE_CHANCE_PER_SPEED: f32 = 0.1;
+pub const DEFEND_MANA_RESTORE: isize = 1;
 
 #[derive(Component, PartialEq, Eq, Clone, Copy)]
 pub enum CombatMenuOption {
     Attack,
     MagicAttack,
+    Item,
+    Defend,
     Run,
 }
 
@@ -32,9 +56,33 @@ pub struct DespawnTimer(Timer);
 #[derive(Component)]
 pub struct CombatText;
 
+#[derive(Component)]
+pub struct CombatHealthText;
+
 #[derive(Component)]
 pub struct CombatManaText;
 
+#[derive(Component)]
+pub struct TargetCursor;
+
+/// Halves incoming damage until the holder's next turn
+#[derive(Component)]
+pub struct Defending;
+
+/// Enemy that ran away, it counts as defeated but gives no exp
+#[derive(Component)]
+pub struct Fled;
+
+#[derive(Component)]
+pub struct TurnTimeline;
+
+#[derive(Component)]
+pub struct SpellMenu;
+
+#[derive(Component)]
+pub struct ItemMenu;
+
+
 pub struct CombatPlugin;
 
 pub struct FightEvent {
@@ -42,9 +90,14 @@ pub struct FightEvent {
     attack_type: AttackType,
     damage_amount: isize,
     next_state: CombatState,
+    // Overrides the default effect of the attack type
+    vfx_index: Option<usize>,
+    // Already rolled by the attacker
+    status: Option<StatusKind>,
+    pub outcome: HitOutcome,
 }
 
-#[derive(Component, Inspectable)]
+#[derive(Component, Inspectable, Clone, Serialize, Deserialize)]
 pub struct CombatStats {
     pub health: isize,
     pub max_health: isize,
@@ -52,29 +105,130 @@ pub struct CombatStats {
     pub max_mana: isize,
     pub attack: isize,
     pub defense: isize,
+    pub speed: isize,
+    // Percentages
+    pub accuracy: isize,
+    pub evasion: isize,
+    pub crit_rate: isize,
 }
 
-#[derive(Clone, Copy)]
-pub enum EnemyType {
-    Bat,
-    Ghost,
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HitOutcome {
+    Miss,
+    Normal,
+    Critical,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum AttackType {
     Standard,
     MagicGeneric,
     MagicFire,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Affinity {
+    Normal,
+    Weak,
+    Resist,
+    Immune,
+    Absorb,
+}
+
+impl HitOutcome {
+    /// Rolls a physical attack, spells always land as normal hits
+    pub fn roll(attacker: &CombatStats, defender: &CombatStats, rng: &mut GameRng) -> Self {
+        let hit_chance = (attacker.accuracy - defender.evasion).clamp(5, 100);
+        if !rng.chance(hit_chance as f32 / 100.0) {
+            HitOutcome::Miss
+        } else if rng.chance(attacker.crit_rate as f32 / 100.0) {
+            HitOutcome::Critical
+        } else {
+            HitOutcome::Normal
+        }
+    }
+
+    pub fn feedback_text(self) -> Option<&'static str> {
+        match self {
+            HitOutcome::Miss => Some("Miss"),
+            HitOutcome::Normal => None,
+            HitOutcome::Critical => Some("Critical!"),
+        }
+    }
+}
+
+impl Affinity {
+    /// Damage after the affinity is applied, negative values heal
+    pub fn apply(self, damage: isize) -> isize {
+        match self {
+            Affinity::Normal => damage,
+            Affinity::Weak => damage * 2,
+            Affinity::Resist => damage / 2,
+            Affinity::Immune => 0,
+            Affinity::Absorb => -damage,
+        }
+    }
+
+    pub fn feedback_text(self) -> Option<&'static str> {
+        match self {
+            Affinity::Normal => None,
+            Affinity::Weak => Some("Weak!"),
+            Affinity::Resist => Some("Resist"),
+            Affinity::Immune => Some("Immune"),
+            Affinity::Absorb => Some("Absorb"),
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub struct CombatMenuSelection {
     selected: CombatMenuOption,
 }
 
+#[derive(Clone)]
+pub enum PlayerAction {
+    Attack,
+    Cast(Spell),
+}
+
+pub struct CombatTargetSelection {
+    selected: usize,
+    action: PlayerAction,
+}
+
+pub struct SpellMenuSelection {
+    selected: usize,
+}
+
+pub struct ItemMenuSelection {
+    selected: usize,
+}
+
+/// Set before starting a scripted or boss fight, reset when combat ends
+pub struct BattleRules {
+    pub can_run: bool,
+}
+
+impl Default for BattleRules {
+    fn default() -> Self {
+        BattleRules { can_run: true }
+    }
+}
+
+/// Combatants that still have to act this round, front acts next
+#[derive(Default)]
+pub struct TurnOrder {
+    queue: VecDeque<Entity>,
+    current: Option<Entity>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum CombatState {
+    NextTurn,
     PlayerTurn,
+    SpellSelect,
+    ItemSelect,
+    PlayerTargeting,
     PlayerAttack,
     EnemyTurn(bool),
     EnemyAttack,
@@ -84,6 +238,7 @@ pub enum CombatState {
 
 pub struct AttackEffects {
     timer: Timer,
+    targets: Vec<Entity>,
     flash_speed: f32,
     screen_shake_amount: f32,
     current_shake: f32,
@@ -95,6 +250,7 @@ impl Plugin for CombatPlugin {
             .add_state(CombatState::PlayerTurn)
             .insert_resource(AttackEffects {
                 timer: Timer::from_seconds(0.7, true),
+                targets: Vec::new(),
                 flash_speed: 0.1,
                 screen_shake_amount: 0.1,
                 current_shake: 0.0,
@@ -102,7 +258,18 @@ impl Plugin for CombatPlugin {
             .insert_resource(CombatMenuSelection {
                 selected: CombatMenuOption::Attack,
             })
+            .insert_resource(CombatTargetSelection {
+                selected: 0,
+                action: PlayerAction::Attack,
+            })
+            .insert_resource(SpellMenuSelection { selected: 0 })
+            .insert_resource(ItemMenuSelection { selected: 0 })
+            .init_resource::<TurnOrder>()
+            .init_resource::<BattleRules>()
             .add_system(despawn_system)
+            .add_system_set(
+                SystemSet::on_update(CombatState::NextTurn).with_system(next_turn),
+            )
             .add_system_set(
                 SystemSet::on_update(CombatState::EnemyTurn(false)).with_system(process_enemy_turn),
             )
@@ -111,10 +278,13 @@ impl Plugin for CombatPlugin {
                     .with_system(combat_input)
                     .with_system(combat_damage_calc)
                     .with_system(highlight_combat_buttons)
+                    .with_system(update_turn_timeline)
+                    .with_system(update_stats_texts)
                     .with_system(combat_camera),
             )
             .add_system_set(
                 SystemSet::on_enter(GameState::Combat)
+                    .with_system(apply_area_battle_rules)
                     .with_system(set_starting_state)
                     .with_system(spawn_enemy)
                     .with_system(spawn_player_stats_texts)
@@ -122,10 +292,39 @@ impl Plugin for CombatPlugin {
             )
             .add_system_set(
                 SystemSet::on_exit(GameState::Combat)
+                    .with_system(reset_battle_rules)
+                    .with_system(clear_player_combat_effects)
                     .with_system(despawn_all_combat_text)
                     .with_system(despawn_menu)
                     .with_system(despawn_enemy),
             )
+            .add_system_set(
+                SystemSet::on_enter(CombatState::SpellSelect).with_system(spawn_spell_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(CombatState::SpellSelect).with_system(spell_menu_input),
+            )
+            .add_system_set(
+                SystemSet::on_exit(CombatState::SpellSelect).with_system(despawn_spell_menu),
+            )
+            .add_system_set(
+                SystemSet::on_enter(CombatState::ItemSelect).with_system(spawn_item_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(CombatState::ItemSelect).with_system(item_menu_input),
+            )
+            .add_system_set(
+                SystemSet::on_exit(CombatState::ItemSelect).with_system(despawn_item_menu),
+            )
+            .add_system_set(
+                SystemSet::on_enter(CombatState::PlayerTargeting).with_system(spawn_target_cursor),
+            )
+            .add_system_set(
+                SystemSet::on_update(CombatState::PlayerTargeting).with_system(target_input),
+            )
+            .add_system_set(
+                SystemSet::on_exit(CombatState::PlayerTargeting).with_system(despawn_target_cursor),
+            )
             .add_system_set(
                 SystemSet::on_enter(CombatState::PlayerAttack)
                     .with_system(handle_initial_attack_effects),
@@ -141,6 +340,11 @@ impl Plugin for CombatPlugin {
             .add_system_set(
                 SystemSet::on_update(CombatState::Reward).with_system(handle_accepting_reward),
             )
+            // Hits on the player shake the screen, a miss leaves no target to shake for
+            .add_system_set(
+                SystemSet::on_enter(CombatState::EnemyAttack)
+                    .with_system(handle_initial_attack_effects),
+            )
             .add_system_set(
                 SystemSet::on_update(CombatState::EnemyAttack).with_system(handle_attack_effects),
             );
@@ -165,6 +369,7 @@ fn spawn_player_stats_texts(
     commands
         .entity(health_text)
         .insert(CombatText)
+        .insert(CombatHealthText)
         .insert(Name::new("health_text"));
     commands.entity(player).add_child(health_text);
 
@@ -183,94 +388,514 @@ fn spawn_player_stats_texts(
     commands.entity(player).add_child(mana_text);
 }
 
+/// Rebuilds health and mana texts of anyone whose stats, statuses or guard changed
+fn update_stats_texts(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    stats_query: Query<(
+        Entity,
+        ChangeTrackers<CombatStats>,
+        ChangeTrackers<StatusEffects>,
+        &CombatStats,
+        &StatusEffects,
+        Option<&Defending>,
+        &Children,
+    )>,
+    health_text_query: Query<&Transform, With<CombatHealthText>>,
+    mana_text_query: Query<&Transform, With<CombatManaText>>,
+    // Guarding is a component coming and going, who showed it last time is kept here
+    mut guarded: Local<HashSet<Entity>>,
+) {
+    for (entity, stats_tracker, status_tracker, stats, statuses, defending, children) in stats_query.iter() {
+        let guard_changed = defending.is_some() != guarded.contains(&entity);
+        if !stats_tracker.is_changed() && !status_tracker.is_changed() && !guard_changed {
+            continue;
+        }
+        if defending.is_some() {
+            guarded.insert(entity);
+        } else {
+            guarded.remove(&entity);
+        }
+
+        for child in children.iter() {
+            let (text, transform) = if let Ok(transform) = health_text_query.get(*child) {
+                // Statuses sit next to the health of whoever has them
+                let mut text = format!("Health: {}", stats.health as usize);
+                if !statuses.is_empty() {
+                    text = format!("{} {}", text, statuses.label());
+                }
+                if defending.is_some() {
+                    text = format!("{} Guard", text);
+                }
+                (text, transform)
+            } else if let Ok(transform) = mana_text_query.get(*child) {
+                (format!("Mana: {}", stats.mana as usize), transform)
+            } else {
+                continue;
+            };
+
+            //Delete old text
+            commands.entity(*child).despawn_recursive();
+            //Create new text in the same spot
+            let new_text = spawn_ascii_text(&mut commands, &ascii, &text, transform.translation);
+            if health_text_query.get(*child).is_ok() {
+                commands.entity(new_text).insert(CombatText).insert(CombatHealthText);
+            } else {
+                commands.entity(new_text).insert(CombatManaText);
+            }
+            commands.entity(entity).add_child(new_text);
+        }
+    }
+}
+
 fn handle_initial_attack_effects(
     mut commands: Commands,
     ascii: Res<AsciiSheet>,
     vfx_sheet: Res<VfxSheet>,
-    mut enemy_graphics_query: Query<&Transform, With<Enemy>>,
+    mut attack_fx: ResMut<AttackEffects>,
+    enemy_graphics_query: Query<&Transform, With<Enemy>>,
     mut event_reader: EventReader<FightEvent>,
 ) {
-    let enemy_transform = enemy_graphics_query.iter_mut().next().unwrap();
-    let mut vfx_index = 0;
+    attack_fx.targets.clear();
     for event in event_reader.iter() {
-        vfx_index = match event.attack_type {
+        // Nothing to flash when the attack whiffs
+        if event.outcome == HitOutcome::Miss {
+            continue;
+        }
+        let vfx_index = event.vfx_index.unwrap_or(match event.attack_type {
             AttackType::Standard => vfx_sheet.slash,
             AttackType::MagicGeneric => vfx_sheet.magic,
             AttackType::MagicFire => vfx_sheet.slash,
-        }
-    }
+        });
+        attack_fx.targets.push(event.target);
 
-    let attack_vfx = spawn_ascii_sprite(
-        &mut commands,
-        &ascii,
-        vfx_index,
-        Color::rgb(0.9, 0.9, 0.9),
-        Vec3::new(
-            enemy_transform.translation.x,
-            enemy_transform.translation.y,
-            150.0,
-        ),
-        Vec3::splat(6.0),
-    );
+        let enemy_transform = match enemy_graphics_query.get(event.target) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
 
-    commands
-        .entity(attack_vfx)
-        .insert(DespawnTimer(Timer::from_seconds(0.3, false)));
+        let attack_vfx = spawn_ascii_sprite(
+            &mut commands,
+            &ascii,
+            vfx_index,
+            Color::rgb(0.9, 0.9, 0.9),
+            Vec3::new(
+                enemy_transform.translation.x,
+                enemy_transform.translation.y,
+                150.0,
+            ),
+            Vec3::splat(if event.outcome == HitOutcome::Critical { 9.0 } else { 6.0 }),
+        );
+
+        commands
+            .entity(attack_vfx)
+            .insert(DespawnTimer(Timer::from_seconds(0.3, false)));
+    }
 }
 
 fn handle_attack_effects(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
     mut attack_fx: ResMut<AttackEffects>,
     time: Res<Time>,
-    mut enemy_graphics_query: Query<&mut Visibility, With<Enemy>>,
+    mut enemy_graphics_query: Query<(&mut Visibility, &CombatStats, Option<&Children>), With<Enemy>>,
+    player_query: Query<&CombatStats, With<Player>>,
     mut state: ResMut<State<CombatState>>,
 ) {
     attack_fx.timer.tick(time.delta());
-    let mut enemy_sprite = enemy_graphics_query.iter_mut().next().unwrap();
 
     if state.current() == &CombatState::PlayerAttack {
-        if attack_fx.timer.elapsed_secs() % attack_fx.flash_speed > attack_fx.flash_speed / 2.0 {
-            enemy_sprite.is_visible = false;
-        } else {
-            enemy_sprite.is_visible = true;
+        let visible =
+            attack_fx.timer.elapsed_secs() % attack_fx.flash_speed <= attack_fx.flash_speed / 2.0;
+        for target in attack_fx.targets.iter() {
+            if let Ok((mut enemy_sprite, _, _)) = enemy_graphics_query.get_mut(*target) {
+                enemy_sprite.is_visible = visible;
+            }
         }
-    } else {
+    } else if !attack_fx.targets.is_empty() {
         attack_fx.current_shake = attack_fx.screen_shake_amount
             * f32::sin(attack_fx.timer.percent() * 2.0 * std::f32::consts::PI);
     }
 
     if attack_fx.timer.just_finished() {
-        enemy_sprite.is_visible = true;
-        if state.current() == &CombatState::PlayerAttack {
-            state.set(CombatState::EnemyTurn(false)).unwrap();
+        // Hide enemies that died during this attack, show everyone else
+        for (mut visibility, stats, children) in enemy_graphics_query.iter_mut() {
+            if stats.health > 0 {
+                visibility.is_visible = true;
+            } else if visibility.is_visible {
+                visibility.is_visible = false;
+                if let Some(children) = children {
+                    for child in children.iter() {
+                        commands.entity(*child).despawn_recursive();
+                    }
+                }
+            }
+        }
+        attack_fx.targets.clear();
+        attack_fx.current_shake = 0.0;
+
+        if player_query.single().health == 0 {
+            state.set(CombatState::Exiting).unwrap();
+            create_transition_fadeout(
+                &mut commands,
+                FadeTransition::Set(GameState::GameOver),
+                &ascii,
+            );
         } else {
-            state.set(CombatState::PlayerTurn).unwrap();
+            state.set(CombatState::NextTurn).unwrap();
         }
     }
 }
 
-fn set_starting_state(mut combat_state: ResMut<State<CombatState>>) {
-    // TODO speed and turn calculations
+/// Living enemies ordered from left to right
+fn living_enemies(enemy_query: &Query<(Entity, &CombatStats, &Transform), With<Enemy>>) -> Vec<Entity> {
+    let mut living: Vec<(Entity, f32)> = enemy_query
+        .iter()
+        .filter(|(_, stats, _)| stats.health > 0)
+        .map(|(entity, _, transform)| (entity, transform.translation.x))
+        .collect();
+    living.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    living.into_iter().map(|(entity, _)| entity).collect()
+}
+
+fn set_starting_state(
+    mut combat_state: ResMut<State<CombatState>>,
+    mut turn_order: ResMut<TurnOrder>,
+) {
+    turn_order.queue.clear();
+    turn_order.current = None;
     // throw away error if it occurs
-    let _ = combat_state.set(CombatState::PlayerTurn);
+    let _ = combat_state.set(CombatState::NextTurn);
+}
+
+/// Orders one round of turns, fastest first. Anyone at least twice as fast
+/// as the other side gets a second turn at the end of the round.
+fn build_round(combatants: &[(Entity, isize, bool)]) -> VecDeque<Entity> {
+    let mut sorted = combatants.to_vec();
+    // Ties go to the player
+    sorted.sort_by_key(|(_, speed, is_enemy)| (-speed, *is_enemy));
+
+    let fastest_speed = |enemies: bool| {
+        sorted
+            .iter()
+            .filter(|(_, _, is_enemy)| *is_enemy == enemies)
+            .map(|(_, speed, _)| *speed)
+            .max()
+            .unwrap_or(0)
+    };
+    let fastest_enemy = fastest_speed(true);
+    let player_speed = fastest_speed(false);
+
+    let mut round: VecDeque<Entity> = sorted.iter().map(|(entity, _, _)| *entity).collect();
+    for (entity, speed, is_enemy) in sorted.iter() {
+        let opponent_speed = if *is_enemy { player_speed } else { fastest_enemy };
+        if *speed >= 2 * opponent_speed.max(1) {
+            round.push_back(*entity);
+        }
+    }
+    round
+}
+
+fn living_combatants(combatant_query: &Query<(Entity, &CombatStats, Option<&Enemy>)>) -> Vec<(Entity, isize, bool)> {
+    combatant_query
+        .iter()
+        .filter(|(_, stats, _)| stats.health > 0)
+        .map(|(entity, stats, enemy)| (entity, stats.speed, enemy.is_some()))
+        .collect()
+}
+
+fn next_turn(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    mut combat_state: ResMut<State<CombatState>>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut log: EventWriter<CombatLogEvent>,
+    mut combatant_query: Query<(
+        Entity,
+        &mut CombatStats,
+        &mut StatusEffects,
+        Option<&Enemy>,
+        &Transform,
+    )>,
+) {
+    // End of the previous turn
+    if let Some(previous) = turn_order.current.take() {
+        if let Ok((_, _, mut statuses, _, _)) = combatant_query.get_mut(previous) {
+            if !statuses.is_empty() {
+                statuses.on_turn_end();
+            }
+        }
+    }
+
+    let living: Vec<(Entity, isize, bool)> = combatant_query
+        .iter()
+        .filter(|(_, stats, _, _, _)| stats.health > 0)
+        .map(|(entity, stats, _, enemy, _)| (entity, stats.speed, enemy.is_some()))
+        .collect();
+    // Enemies might not be spawned yet on the first frame of combat
+    if !combatant_query.iter().any(|(_, _, _, enemy, _)| enemy.is_some()) {
+        return;
+    }
+    // The last one standing fled, or fell to a status, before another turn came up
+    if !living.iter().any(|(_, _, is_enemy)| *is_enemy) {
+        combat_state.set(CombatState::Reward).unwrap();
+        return;
+    }
+
+    let (next, is_enemy) = loop {
+        if turn_order.queue.is_empty() {
+            turn_order.queue = build_round(&living);
+        }
+        let next = turn_order.queue.pop_front().unwrap();
+        // Skip anyone who died before their turn came
+        if let Some((_, _, is_enemy)) = living.iter().find(|(entity, _, _)| *entity == next) {
+            break (next, *is_enemy);
+        }
+    };
+    turn_order.current = Some(next);
+
+    // Start of the new turn, guarding only lasts until then
+    commands.entity(next).remove::<Defending>();
+    let (_, mut stats, statuses, enemy, transform) = combatant_query.get_mut(next).unwrap();
+    let name = enemy.map_or(PLAYER_NAME, |enemy| enemy.definition.name.as_str());
+    let outcome = statuses.on_turn_start(&stats);
+    if outcome.damage > 0 {
+        stats.health = std::cmp::max(stats.health - outcome.damage, 0);
+    }
+    if !outcome.feedback.is_empty() {
+        spawn_feedback_text(
+            &mut commands,
+            &ascii,
+            &outcome.feedback.join(" "),
+            feedback_position(transform, is_enemy),
+        );
+
+        let mut message = format!("{}!", outcome.feedback.join(" "));
+        if outcome.damage > 0 {
+            message += &format!(" {} {} damage.", conjugate(name, "take"), outcome.damage);
+        }
+        if stats.health == 0 {
+            message += &format!(" {}.", conjugate(name, "fall"));
+        } else if outcome.skip_turn {
+            message += &format!(" {} can't move.", name);
+        }
+        log.send(CombatLogEvent(message));
+    }
+    let holder_dead = stats.health == 0;
+
+    let all_enemies_dead = combatant_query
+        .iter()
+        .filter(|(_, _, _, enemy, _)| enemy.is_some())
+        .all(|(_, stats, _, _, _)| stats.health == 0);
+
+    if all_enemies_dead {
+        combat_state.set(CombatState::Reward).unwrap();
+    } else if holder_dead || outcome.skip_turn {
+        // Pause on the status feedback, the attack effects clean up after it
+        combat_state.set(CombatState::PlayerAttack).unwrap();
+    } else if is_enemy {
+        combat_state.set(CombatState::EnemyTurn(false)).unwrap();
+    } else {
+        combat_state.set(CombatState::PlayerTurn).unwrap();
+    }
+}
+
+/// Where floating feedback goes, the player's sprite is hidden during combat
+fn feedback_position(transform: &Transform, is_enemy: bool) -> Vec3 {
+    if is_enemy {
+        Vec3::new(transform.translation.x, transform.translation.y - 0.35, 150.0)
+    } else {
+        player_feedback_position()
+    }
+}
+
+/// Above the battle log
+fn player_feedback_position() -> Vec3 {
+    Vec3::new(-RESOLUTION / 2.0, -1.0 + 12.0 * TILE_SIZE, 150.0)
+}
+
+fn spawn_feedback_text(commands: &mut Commands, ascii: &AsciiSheet, text: &str, center: Vec3) {
+    let text_entity = spawn_ascii_text(
+        commands,
+        ascii,
+        text,
+        center - Vec3::new((text.len() / 2) as f32 * TILE_SIZE, 0.0, 0.0),
+    );
+    commands
+        .entity(text_entity)
+        .insert(DespawnTimer(Timer::from_seconds(1.0, false)));
+}
+
+fn update_turn_timeline(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    turn_order: Res<TurnOrder>,
+    combatant_query: Query<(Entity, &CombatStats, Option<&Enemy>)>,
+    name_query: Query<&Name>,
+    timeline_query: Query<Entity, With<TurnTimeline>>,
+) {
+    if !turn_order.is_changed() {
+        return;
+    }
+    for timeline in timeline_query.iter() {
+        commands.entity(timeline).despawn_recursive();
+    }
+
+    let living = living_combatants(&combatant_query);
+    let is_living = |entity: &Entity| living.iter().any(|(living, _, _)| living == entity);
+
+    // Current turn, the rest of this round and a preview of the next one
+    let upcoming: Vec<Entity> = turn_order
+        .current
+        .iter()
+        .chain(turn_order.queue.iter())
+        .copied()
+        .chain(build_round(&living))
+        .filter(is_living)
+        .take(TIMELINE_LENGTH)
+        .collect();
+    if upcoming.is_empty() {
+        return;
+    }
+
+    let names: Vec<String> = upcoming
+        .iter()
+        .map(|entity| match combatant_query.get(*entity) {
+            Ok((_, _, None)) => "You".to_string(),
+            _ => name_query
+                .get(*entity)
+                .map(|name| name.as_str().to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+    let timeline_text = format!("Turn: {}", names.join(" > "));
+    let timeline = spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &timeline_text,
+        Vec3::new(-RESOLUTION + TILE_SIZE, 1.0 - TILE_SIZE, 100.0),
+    );
+    commands
+        .entity(timeline)
+        .insert(TurnTimeline)
+        .insert(CombatText);
 }
 
 fn process_enemy_turn(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
     mut fight_event: EventWriter<FightEvent>,
     mut combat_state: ResMut<State<CombatState>>,
-    enemy_query: Query<&CombatStats, With<Enemy>>,
-    player_query: Query<Entity, With<Player>>,
+    turn_order: Res<TurnOrder>,
+    mut enemy_query: Query<(Entity, &mut CombatStats, &Enemy, &Transform), Without<Player>>,
+    player_query: Query<(Entity, &CombatStats), With<Player>>,
+    spell_definitions: Res<SpellDefinitions>,
+    spell_lists: Res<Assets<SpellList>>,
+    mut rng: ResMut<GameRng>,
+    mut log: EventWriter<CombatLogEvent>,
 ) {
-    let player_ent = player_query.single();
-    // TODO support multiple enemies
-    let enemy_stats = enemy_query.iter().next().unwrap();
+    let (player_ent, player_stats) = player_query.single();
+    let (enemy_ent, mut enemy_stats, enemy, transform) = match turn_order
+        .current
+        .and_then(|enemy| enemy_query.get_mut(enemy).ok())
+    {
+        Some(enemy) => enemy,
+        None => {
+            combat_state.set(CombatState::NextTurn).unwrap();
+            return;
+        }
+    };
+
+    let find_spell = |name: &str| {
+        spell_lists
+            .get(&spell_definitions.0)
+            .and_then(|spells| spells.spells.iter().find(|spell| spell.name == name))
+    };
+    let action = enemy
+        .definition
+        .ai
+        .choose(&enemy_stats, rng.roll(), |action| match action {
+            EnemyAction::Cast(name) => {
+                find_spell(name).is_some_and(|spell| spell.mana_cost <= enemy_stats.mana)
+            }
+            _ => true,
+        });
+
+    let hits_player = match &action {
+        EnemyAction::Attack => true,
+        EnemyAction::Cast(name) => {
+            find_spell(name).is_some_and(|spell| spell.target != SpellTarget::Myself)
+        }
+        _ => false,
+    };
+    let name = enemy.definition.name.as_str();
+    let feedback = match action {
+        EnemyAction::Attack => {
+            log.send(CombatLogEvent(format!("{}!", conjugate(name, "attack"))));
+            fight_event.send(FightEvent {
+                target: player_ent,
+                attack_type: AttackType::Standard,
+                damage_amount: enemy_stats.attack,
+                next_state: CombatState::EnemyAttack,
+                vfx_index: None,
+                outcome: HitOutcome::roll(&enemy_stats, player_stats, &mut rng),
+                status: enemy
+                    .definition
+                    .attack_status
+                    .filter(|(_, chance)| rng.chance(*chance))
+                    .map(|(status, _)| status),
+            });
+            combat_state.set(CombatState::EnemyTurn(true)).unwrap();
+            return;
+        }
+        EnemyAction::Cast(spell_name) => {
+            let spell = find_spell(&spell_name).expect("Enemy casting unknown spell");
+            cast_spell(
+                spell,
+                &[player_ent],
+                &mut enemy_stats,
+                &mut fight_event,
+                CombatState::EnemyAttack,
+                name,
+                &mut log,
+            );
+            spell.name.clone()
+        }
+        EnemyAction::Defend => {
+            commands.entity(enemy_ent).insert(Defending);
+            log.send(CombatLogEvent(format!("{}.", conjugate(name, "defend"))));
+            "Defend".to_string()
+        }
+        EnemyAction::Buff => {
+            enemy_stats.attack += 1;
+            log.send(CombatLogEvent(format!("{}'s attack rises.", name)));
+            "Atk up".to_string()
+        }
+        EnemyAction::Flee => {
+            if rng.chance(ENEMY_FLEE_CHANCE) {
+                enemy_stats.health = 0;
+                commands.entity(enemy_ent).insert(Fled);
+                log.send(CombatLogEvent(format!("{} fled!", name)));
+                "Fled".to_string()
+            } else {
+                log.send(CombatLogEvent(format!("{} tries to flee.", name)));
+                "Can't flee".to_string()
+            }
+        }
+    };
 
-    fight_event.send(FightEvent {
-        target: player_ent,
-        attack_type: AttackType::Standard,
-        damage_amount: enemy_stats.attack,
-        next_state: CombatState::EnemyAttack,
-    });
-    combat_state.set(CombatState::EnemyTurn(true)).unwrap();
+    spawn_feedback_text(
+        &mut commands,
+        &ascii,
+        &feedback,
+        feedback_position(transform, true),
+    );
+    if hits_player {
+        combat_state.set(CombatState::EnemyTurn(true)).unwrap();
+    } else {
+        // Nothing to hit, just pause on the feedback text
+        combat_state.set(CombatState::PlayerAttack).unwrap();
+    }
 }
 
 fn handle_accepting_reward(
@@ -285,35 +910,125 @@ fn handle_accepting_reward(
     }
 }
 
+/// "Attack 2>4" for every stat that went up, a few per line
+fn stat_change_lines(before: &StatBlock, after: &StatBlock) -> Vec<String> {
+    let changes: Vec<String> = before
+        .entries()
+        .iter()
+        .zip(after.entries().iter())
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((label, old), (_, new))| format!("{} {}>{}", label, old, new))
+        .collect();
+    changes.chunks(3).map(|chunk| chunk.join("  ")).collect()
+}
+
 fn give_reward(
     mut commands: Commands,
     ascii: Res<AsciiSheet>,
-    mut player_query: Query<(&mut Player, &mut CombatStats)>,
-    enemy_query: Query<&Enemy>,
+    mut player_query: Query<(
+        &mut Player,
+        &mut BaseStats,
+        &mut CombatStats,
+        &mut Spellbook,
+        &Equipment,
+        &mut Inventory,
+    )>,
+    enemy_query: Query<&Enemy, Without<Fled>>,
+    spell_definitions: Res<SpellDefinitions>,
+    spell_lists: Res<Assets<SpellList>>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    progression_definition: Res<ProgressionDefinition>,
+    progressions: Res<Assets<Progression>>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let exp_reward = match enemy_query.single().enemy_type {
-        EnemyType::Bat => 10,
-        EnemyType::Ghost => 30,
+    let exp_reward = enemy_query
+        .iter()
+        .map(|enemy| enemy.definition.exp)
+        .sum();
+    let gold_reward: usize = enemy_query
+        .iter()
+        .map(|enemy| enemy.definition.gold)
+        .sum();
+    let mut lines = vec![format!("Earned {} exp and {} gold", exp_reward, gold_reward)];
+
+    let (mut player, mut base_stats, mut stats, mut spellbook, equipment, mut inventory) =
+        player_query.single_mut();
+    player.gold += gold_reward;
+
+    // One roll per enemy, the same item from several adds up into one line
+    let mut drops: Vec<(String, usize)> = Vec::new();
+    for enemy in enemy_query.iter() {
+        if let Some((item, count)) = enemy.definition.loot.roll(&mut rng) {
+            match drops.iter_mut().find(|(name, _)| *name == item) {
+                Some((_, total)) => *total += count,
+                None => drops.push((item, count)),
+            }
+        }
+    }
+    if let Some(items) = item_lists.get(&item_definitions.0) {
+        for (name, count) in drops {
+            let item = match items.get(&name) {
+                Some(item) => item,
+                None => continue,
+            };
+            let added = inventory.add(item, count);
+            if added > 0 {
+                lines.push(format!("Found {} {}", added, name));
+            }
+            if added < count {
+                lines.push(format!("No room for {} {}", count - added, name));
+            }
+        }
+    }
+    let progression = match progressions.get(&progression_definition.0) {
+        Some(progression) => progression,
+        None => {
+            // Levels get sorted out after the next fight
+            player.exp += exp_reward;
+            spawn_reward_lines(&mut commands, &ascii, &lines);
+            return;
+        }
     };
-    let reward_text = format!("Earned {} exp", exp_reward);
-    let text = spawn_ascii_text(
-        &mut commands,
-        &ascii,
-        &reward_text,
-        Vec3::new(-((reward_text.len() / 2) as f32 * TILE_SIZE), 0.0, 0.0),
-    );
+    let level_ups = player.give_exp(exp_reward, &mut base_stats, &mut stats, progression);
+    for level_up in level_ups.iter() {
+        let mut level_text = format!("Level {}!", level_up.level);
+        // One new spell for every level
+        if let Some(spell) = spell_lists
+            .get(&spell_definitions.0)
+            .and_then(|spells| spellbook.learn_next(spells))
+        {
+            level_text.push_str(&format!(" Learned {}!", spell.name));
+        }
+        lines.push(level_text);
 
-    commands.entity(text).insert(CombatText);
-    let (mut player, mut stats) = player_query.single_mut();
-    if player.give_exp(exp_reward, &mut stats) {
-        let level_text = "Level up!";
+        // Shown with gear on, same as the equipment screen
+        let (before, after) = match item_lists.get(&item_definitions.0) {
+            Some(items) => (
+                equipment.apply(&level_up.before, items),
+                equipment.apply(&level_up.after, items),
+            ),
+            None => (level_up.before, level_up.after),
+        };
+        lines.extend(stat_change_lines(&before, &after));
+    }
+    lines.push(format!(
+        "Next level in {} exp",
+        progression.exp_to_next(player.level) - player.exp
+    ));
+    spawn_reward_lines(&mut commands, &ascii, &lines);
+}
+
+fn spawn_reward_lines(commands: &mut Commands, ascii: &AsciiSheet, lines: &[String]) {
+    for (i, line) in lines.iter().enumerate() {
         let text = spawn_ascii_text(
-            &mut commands,
-            &ascii,
-            level_text,
+            commands,
+            ascii,
+            line,
             Vec3::new(
-                -((level_text.len() / 2) as f32 * TILE_SIZE),
-                -1.5 * TILE_SIZE,
+                -((line.len() / 2) as f32 * TILE_SIZE),
+                // Starts high so a few level ups fit above the battle log
+                0.6 - i as f32 * 1.25 * TILE_SIZE,
                 0.0,
             ),
         );
@@ -321,6 +1036,25 @@ fn give_reward(
     }
 }
 
+// A scripted fight that already forbids running stays that way
+fn apply_area_battle_rules(mut battle_rules: ResMut<BattleRules>, area: Res<CurrentArea>) {
+    battle_rules.can_run &= area.encounters.can_run;
+}
+
+fn reset_battle_rules(mut battle_rules: ResMut<BattleRules>) {
+    *battle_rules = BattleRules::default();
+}
+
+fn clear_player_combat_effects(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut StatusEffects), With<Player>>,
+) {
+    for (player, mut statuses) in player_query.iter_mut() {
+        statuses.clear();
+        commands.entity(player).remove::<Defending>();
+    }
+}
+
 fn despawn_menu(mut commands: Commands, button_query: Query<Entity, With<CombatMenuOption>>) {
     for button in button_query.iter() {
         commands.entity(button).despawn_recursive();
@@ -333,49 +1067,77 @@ fn despawn_all_combat_text(mut commands: Commands, text_query: Query<Entity, Or<
     }
 }
 
-fn spawn_enemy(mut commands: Commands, ascii: Res<AsciiSheet>, characters: Res<CharacterSheet>) {
-    let enemy_type = match rand::random::<f32>() {
-        x if x < 0.5 => EnemyType::Bat,
-        _ => EnemyType::Ghost,
-    };
-    let stats = match enemy_type {
-        EnemyType::Bat => CombatStats {
-            health: 3,
-            max_health: 3,
-            mana: 0,
-            max_mana: 0,
-            attack: 2,
-            defense: 1,
-        },
-        EnemyType::Ghost => CombatStats {
-            health: 5,
-            max_health: 5,
-            mana: 0,
-            max_mana: 0,
-            attack: 3,
-            defense: 2,
-        },
-    };
+fn spawn_enemy(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    characters: Res<CharacterSheet>,
+    enemy_definitions: Res<EnemyDefinitions>,
+    enemy_lists: Res<Assets<EnemyList>>,
+    area: Res<CurrentArea>,
+    mut rng: ResMut<GameRng>,
+) {
+    let enemies = enemy_lists
+        .get(&enemy_definitions.0)
+        .expect("Enemy definitions not loaded, check data/game.enemies.ron");
+    let enemy_count = 1 + rng.range(0..MAX_ENEMIES);
+
+    // A typo in the map shouldn't end the game, dreadblaze-mapcheck points these out
+    let unknown: Vec<&String> = area
+        .encounters
+        .enemies
+        .iter()
+        .filter(|name| !enemies.is_defined(name))
+        .collect();
+    if !unknown.is_empty() {
+        warn!("{} lists enemies that aren't defined: {:?}", area.name, unknown);
+    }
+
+    for i in 0..enemy_count {
+        let roll = rng.roll();
+        let definition = enemies
+            .pick(roll, &area.encounters.enemies)
+            .or_else(|| enemies.pick(roll, &[]))
+            .expect("No enemies defined, check data/game.enemies.ron");
+        // Spread enemies evenly around the center of the screen
+        let x = (i as f32 - (enemy_count - 1) as f32 / 2.0) * ENEMY_SPACING;
+        spawn_single_enemy(&mut commands, &ascii, &characters, definition, x);
+    }
+}
+
+fn spawn_single_enemy(
+    commands: &mut Commands,
+    ascii: &AsciiSheet,
+    characters: &CharacterSheet,
+    definition: &EnemyDefinition,
+    x: f32,
+) {
+    let stats = definition.stats.to_combat_stats();
 
     let health_text = spawn_ascii_text(
-        &mut commands,
-        &ascii,
+        commands,
+        ascii,
         &format!("Health: {}", stats.health as usize),
         //relative to enemy pos
         Vec3::new(-4.5 * TILE_SIZE, 0.5, 100.0),
     );
-    commands.entity(health_text).insert(CombatText);
+    commands
+        .entity(health_text)
+        .insert(CombatText)
+        .insert(CombatHealthText);
     let sprite = spawn_enemy_sprite(
-        &mut commands,
-        &characters,
-        Vec3::new(0.0, 0.3, 100.0),
-        enemy_type,
+        commands,
+        characters,
+        Vec3::new(x, 0.3, 100.0),
+        definition,
     );
     commands
         .entity(sprite)
-        .insert(Enemy { enemy_type })
+        .insert(Enemy {
+            definition: definition.clone(),
+        })
         .insert(stats)
-        .insert(Name::new("Bat"))
+        .insert(StatusEffects::default())
+        .insert(Name::new(definition.name.clone()))
         .add_child(health_text);
 }
 
@@ -443,114 +1205,171 @@ fn spawn_combat_menu(
     ascii: Res<AsciiSheet>,
     nine_slice_indices: Res<NineSliceIndices>,
 ) {
-    // TODO rework spawning combat menu in loop
-
     let box_height = 3.0;
     let box_center_y = -1.0 + box_height * TILE_SIZE / 2.0;
 
-    let run_text = "Run";
-    let run_width = (run_text.len() + 2) as f32;
-    let run_center_x = RESOLUTION - (run_width * TILE_SIZE) / 2.0;
-    spawn_combat_button(
-        &mut commands,
-        &ascii,
-        &nine_slice_indices,
-        Vec3::new(run_center_x, box_center_y, 100.0),
-        run_text,
-        CombatMenuOption::Run,
-        Vec2::new(run_width, box_height),
-    );
+    let buttons = [
+        ("Attack", CombatMenuOption::Attack),
+        ("Magic", CombatMenuOption::MagicAttack),
+        ("Item", CombatMenuOption::Item),
+        ("Defend", CombatMenuOption::Defend),
+        ("Run", CombatMenuOption::Run),
+    ];
 
-    let magic_text = "Magic";
-    let magic_width = (magic_text.len() + 2) as f32;
-    let magic_center_x = RESOLUTION - (run_width * TILE_SIZE) - (magic_width * TILE_SIZE / 2.0);
-    spawn_combat_button(
-        &mut commands,
-        &ascii,
-        &nine_slice_indices,
-        Vec3::new(magic_center_x, box_center_y, 100.0),
-        magic_text,
-        CombatMenuOption::MagicAttack,
-        Vec2::new(magic_width, box_height),
-    );
-
-    let attack_text = "Attack";
-    let attack_width = (attack_text.len() + 2) as f32;
-    let attack_center_x = RESOLUTION
-        - (run_width * TILE_SIZE)
-        - (magic_width * TILE_SIZE)
-        - (attack_width * TILE_SIZE / 2.0);
-    spawn_combat_button(
-        &mut commands,
-        &ascii,
-        &nine_slice_indices,
-        Vec3::new(attack_center_x, box_center_y, 100.0),
-        attack_text,
-        CombatMenuOption::Attack,
-        Vec2::new(attack_width, box_height),
-    );
+    // Laid out from the right edge of the screen leftwards
+    let mut right_edge = RESOLUTION;
+    for (text, option) in buttons.iter().rev() {
+        let width = (text.len() + 2) as f32;
+        let center_x = right_edge - width * TILE_SIZE / 2.0;
+        spawn_combat_button(
+            &mut commands,
+            &ascii,
+            &nine_slice_indices,
+            Vec3::new(center_x, box_center_y, 100.0),
+            text,
+            *option,
+            Vec2::new(width, box_height),
+        );
+        right_edge -= width * TILE_SIZE;
+    }
 }
 
 fn combat_damage_calc(
     mut commands: Commands,
+    ascii: Res<AsciiSheet>,
     mut fight_event: EventReader<FightEvent>,
     //Not necssacarily enemy
-    mut enemy_query: Query<(&Children, &mut CombatStats)>,
-    ascii: Res<AsciiSheet>,
-    text_query: Query<&Transform, With<CombatText>>,
+    mut enemy_query: Query<(
+        &mut CombatStats,
+        Option<&Enemy>,
+        &Transform,
+        &mut StatusEffects,
+        Option<&Defending>,
+    )>,
     mut combat_state: ResMut<State<CombatState>>,
+    mut rng: ResMut<GameRng>,
+    mut log: EventWriter<CombatLogEvent>,
 ) {
-    if let Some(fight_event) = fight_event.iter().next() {
-        //Get target stats and children
-        let (target_children, mut stats) = enemy_query
+    //Spells can hit several targets in the same frame
+    let mut next_state = None;
+    for fight_event in fight_event.iter() {
+        //Get target stats
+        let (mut stats, enemy, transform, mut statuses, defending) = enemy_query
             .get_mut(fight_event.target)
             .expect("Fighting enemy without stats");
 
+        next_state = Some(fight_event.next_state);
+        let name = enemy.map_or(PLAYER_NAME, |enemy| enemy.definition.name.as_str());
+
+        if fight_event.outcome == HitOutcome::Miss {
+            spawn_feedback_text(
+                &mut commands,
+                &ascii,
+                "Miss",
+                feedback_position(transform, enemy.is_some()),
+            );
+            log.send(CombatLogEvent(format!("Miss! {} unharmed.", conjugate(name, "stay"))));
+            continue;
+        }
+
         //Damage calc
-        stats.health = std::cmp::max(
-            stats.health - (fight_event.damage_amount - stats.defense),
-            0,
-        );
+        let affinity = enemy.map_or(Affinity::Normal, |enemy| {
+            enemy.definition.affinity(fight_event.attack_type)
+        });
+        let mut damage = std::cmp::max(fight_event.damage_amount - stats.defense, 0);
+        if fight_event.outcome == HitOutcome::Critical {
+            damage = std::cmp::max(damage * 2, 1);
+        }
+        damage = affinity.apply(damage);
+        if defending.is_some() && damage > 0 {
+            damage /= 2;
+        }
+        stats.health = (stats.health - damage).clamp(0, stats.max_health);
+        if damage > 0 {
+            statuses.on_damage_taken();
+        }
 
-        //Update health
-        for child in target_children.iter() {
-            //See if this child is the health text
-            if let Ok(transform) = text_query.get(*child) {
-                //Delete old text
-                commands.entity(*child).despawn_recursive();
-                //Create new text
-                let new_health = spawn_ascii_text(
-                    &mut commands,
-                    &ascii,
-                    &format!("Health: {}", stats.health as usize),
-                    //relative to enemy pos
-                    transform.translation,
-                );
-                commands.entity(new_health).insert(CombatText);
-                commands.entity(fight_event.target).add_child(new_health);
+        //Fire can set the target alight on top of whatever the attack inflicts
+        let burn = matches!(fight_event.attack_type, AttackType::MagicFire)
+            && rng.chance(BURN_CHANCE);
+        let shrugged_off = matches!(affinity, Affinity::Immune | Affinity::Absorb);
+        let mut inflicted = Vec::new();
+        if stats.health > 0 && !shrugged_off {
+            if let Some(status) = fight_event.status {
+                statuses.inflict(status);
+                inflicted.push(status);
             }
+            if burn {
+                statuses.inflict(StatusKind::Burn);
+                inflicted.push(StatusKind::Burn);
+            }
+        }
+
+        //Let the player know what works
+        let feedback: Vec<&str> = fight_event
+            .outcome
+            .feedback_text()
+            .into_iter()
+            .chain(affinity.feedback_text())
+            .collect();
+        if !feedback.is_empty() {
+            spawn_feedback_text(
+                &mut commands,
+                &ascii,
+                &feedback.join(" "),
+                feedback_position(transform, enemy.is_some()),
+            );
         }
 
-        //Kill enemy if dead
-        //TODO support multiple enemies
+        let mut message: String = feedback.iter().map(|text| format!("{} ", text)).collect();
+        message += &match damage {
+            0 => format!("{} no damage.", conjugate(name, "take")),
+            damage if damage < 0 => format!("{} {} health.", conjugate(name, "recover"), -damage),
+            damage => format!("{} {} damage.", conjugate(name, "take"), damage),
+        };
+        for status in inflicted {
+            message += &format!(" {} got {}.", name, status.inflicted_text());
+        }
         if stats.health == 0 {
+            message += &format!(" {}.", conjugate(name, "fall"));
+        }
+        log.send(CombatLogEvent(message));
+    }
+
+    if let Some(next_state) = next_state {
+        let all_enemies_dead = enemy_query
+            .iter()
+            .filter(|(_, enemy, _, _, _)| enemy.is_some())
+            .all(|(stats, _, _, _, _)| stats.health == 0);
+
+        //Win once every enemy is dead, a dead player is handled after the hit effects
+        if all_enemies_dead {
             combat_state.set(CombatState::Reward).unwrap();
         } else {
-            combat_state.set(fight_event.next_state).unwrap();
+            combat_state.set(next_state).unwrap();
         }
     }
 }
 
 fn combat_input(
     mut commands: Commands,
-    keyboard: Res<Input<KeyCode>>,
-    mut fight_event_writer: EventWriter<FightEvent>,
-    mut player_query: Query<(&mut CombatStats, &Children, Entity), With<Player>>,
-    enemy_query: Query<Entity, With<Enemy>>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut player_query: Query<
+        (Entity, &Spellbook, &Inventory, &mut CombatStats),
+        (With<Player>, Without<Enemy>),
+    >,
+    enemy_query: Query<&CombatStats, With<Enemy>>,
+    battle_rules: Res<BattleRules>,
     mut menu_state: ResMut<CombatMenuSelection>,
+    mut target_state: ResMut<CombatTargetSelection>,
     ascii: Res<AsciiSheet>,
-    combat_state: Res<State<CombatState>>,
-    mana_text: Query<&Transform, With<CombatManaText>>,
+    spell_definitions: Res<SpellDefinitions>,
+    spell_lists: Res<Assets<SpellList>>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut combat_state: ResMut<State<CombatState>>,
+    mut rng: ResMut<GameRng>,
+    mut log: EventWriter<CombatLogEvent>,
 ) {
     if combat_state.current() != &CombatState::PlayerTurn {
         return;
@@ -569,60 +1388,483 @@ fn combat_input(
     menu_state.selected = match new_selection {
         0 => CombatMenuOption::Attack,
         1 => CombatMenuOption::MagicAttack,
-        2 => CombatMenuOption::Run,
+        2 => CombatMenuOption::Item,
+        3 => CombatMenuOption::Defend,
+        4 => CombatMenuOption::Run,
         _ => unreachable!("Bad menu selection"),
     };
 
     if keyboard.just_pressed(KeyCode::Return) {
         match menu_state.selected {
             CombatMenuOption::Attack => {
-                let (player_stats, player_children, player_entity) = player_query.single();
-                // TODO handle multiple enemies and enemy selection
-                let target = enemy_query.iter().next().unwrap();
+                target_state.action = PlayerAction::Attack;
+                combat_state.set(CombatState::PlayerTargeting).unwrap();
+            }
+            CombatMenuOption::MagicAttack => {
+                let (_, spellbook, _, _) = player_query.single();
+                let knows_any_spell = spell_lists
+                    .get(&spell_definitions.0)
+                    .is_some_and(|spells| spells.known(spellbook).next().is_some());
+                if knows_any_spell {
+                    combat_state.set(CombatState::SpellSelect).unwrap();
+                }
+            }
+            CombatMenuOption::Item => {
+                let (_, _, inventory, _) = player_query.single();
+                let has_usable_item = item_lists
+                    .get(&item_definitions.0)
+                    .is_some_and(|items| !inventory.usable_stacks(items).is_empty());
+                if has_usable_item {
+                    combat_state.set(CombatState::ItemSelect).unwrap();
+                }
+            }
+            CombatMenuOption::Defend => {
+                let (player, _, _, mut stats) = player_query.single_mut();
+                stats.mana = (stats.mana + DEFEND_MANA_RESTORE).min(stats.max_mana);
+                commands.entity(player).insert(Defending);
+                spawn_feedback_text(&mut commands, &ascii, "Defend", player_feedback_position());
+                log.send(CombatLogEvent(format!("{}.", conjugate(PLAYER_NAME, "defend"))));
+                // Nothing to hit, pause on the feedback and pass the turn on
+                combat_state.set(CombatState::PlayerAttack).unwrap();
+            }
+            CombatMenuOption::Run => {
+                if !battle_rules.can_run {
+                    // Doesn't cost the turn, the player just has to pick something else
+                    spawn_feedback_text(
+                        &mut commands,
+                        &ascii,
+                        "Can't run!",
+                        player_feedback_position(),
+                    );
+                    log.send(CombatLogEvent("There's no running from this fight!".to_string()));
+                } else if rng.chance(escape_chance(player_query.single().3, &enemy_query)) {
+                    combat_state.set(CombatState::Exiting).unwrap();
+                    create_fadeout(&mut commands, None, &ascii);
+                    log.send(CombatLogEvent("You got away safely.".to_string()));
+                } else {
+                    spawn_feedback_text(
+                        &mut commands,
+                        &ascii,
+                        "Couldn't escape",
+                        player_feedback_position(),
+                    );
+                    log.send(CombatLogEvent("You couldn't escape!".to_string()));
+                    // Pause on the text, then the turn passes on
+                    combat_state.set(CombatState::PlayerAttack).unwrap();
+                }
+            }
+        }
+        // The next menu runs in the same frame, don't let it see this press
+        keyboard.clear_just_pressed(KeyCode::Return);
+    }
+}
+
+/// Faster players get away more easily, always some chance either way
+fn escape_chance(player_stats: &CombatStats, enemy_query: &Query<&CombatStats, With<Enemy>>) -> f32 {
+    let fastest_enemy = enemy_query
+        .iter()
+        .filter(|stats| stats.health > 0)
+        .map(|stats| stats.speed)
+        .max()
+        .unwrap_or(0);
+    let chance = BASE_ESCAPE_CHANCE
+        + (player_stats.speed - fastest_enemy) as f32 * ESCAPE_CHANCE_PER_SPEED;
+    chance.clamp(0.1, 0.95)
+}
+
+/// Spends the mana and sends the hits, spells on yourself take effect straight away
+fn cast_spell(
+    spell: &Spell,
+    targets: &[Entity],
+    caster_stats: &mut CombatStats,
+    fight_event_writer: &mut EventWriter<FightEvent>,
+    next_state: CombatState,
+    caster_name: &str,
+    log: &mut EventWriter<CombatLogEvent>,
+) {
+    caster_stats.mana -= spell.mana_cost;
+    log.send(CombatLogEvent(format!("{} {}.", conjugate(caster_name, "cast"), spell.name)));
+
+    if spell.target == SpellTarget::Myself {
+        let healed = (caster_stats.health + spell.power).min(caster_stats.max_health);
+        log.send(CombatLogEvent(format!(
+            "{} {} health.",
+            conjugate(caster_name, "recover"),
+            healed - caster_stats.health
+        )));
+        caster_stats.health = healed;
+        return;
+    }
+    for target in targets {
+        fight_event_writer.send(FightEvent {
+            target: *target,
+            attack_type: spell.element,
+            damage_amount: spell.power,
+            next_state: next_state,
+            vfx_index: Some(spell.vfx_index),
+            status: spell.status,
+            outcome: HitOutcome::Normal,
+        });
+    }
+}
+
+fn spawn_spell_menu(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    nine_slice_indices: Res<NineSliceIndices>,
+    mut spell_selection: ResMut<SpellMenuSelection>,
+    player_query: Query<(&CombatStats, &Spellbook), With<Player>>,
+    spell_definitions: Res<SpellDefinitions>,
+    spell_lists: Res<Assets<SpellList>>,
+) {
+    let (stats, spellbook) = player_query.single();
+    let spells: Vec<&Spell> = match spell_lists.get(&spell_definitions.0) {
+        Some(spells) => spells.known(spellbook).collect(),
+        None => return,
+    };
+    if spell_selection.selected >= spells.len() {
+        spell_selection.selected = 0;
+    }
+
+    let name_width = spells.iter().map(|spell| spell.name.len()).max().unwrap_or(0);
+    let lines: Vec<(String, Color)> = spells
+        .iter()
+        .map(|spell| {
+            let line = format!("{:<width$} {}mp", spell.name, spell.mana_cost, width = name_width);
+            // Spells the player can't afford are greyed out
+            let color = if spell.mana_cost <= stats.mana {
+                Color::rgb(0.8, 0.8, 0.8)
+            } else {
+                Color::rgb(0.4, 0.4, 0.4)
+            };
+            (line, color)
+        })
+        .collect();
+
+    let size = list_menu_size(&lines);
+    let center = Vec3::new(
+        RESOLUTION - size.x * TILE_SIZE / 2.0,
+        -1.0 + 3.0 * TILE_SIZE + size.y * TILE_SIZE / 2.0,
+        100.0,
+    );
+    let menu = spawn_list_menu(
+        &mut commands,
+        &ascii,
+        &nine_slice_indices,
+        &lines,
+        spell_selection.selected,
+        center,
+    );
+    commands
+        .entity(menu)
+        .insert(Name::new("Spell Menu"))
+        .insert(SpellMenu);
+}
+
+fn despawn_spell_menu(mut commands: Commands, menu_query: Query<Entity, With<SpellMenu>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
 
+fn spell_menu_input(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut fight_event_writer: EventWriter<FightEvent>,
+    mut player_query: Query<(&mut CombatStats, &Spellbook), (With<Player>, Without<Enemy>)>,
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+    mut cursor_query: Query<&mut Transform, (With<ListMenuCursor>, Without<Enemy>)>,
+    mut spell_selection: ResMut<SpellMenuSelection>,
+    mut target_state: ResMut<CombatTargetSelection>,
+    spell_definitions: Res<SpellDefinitions>,
+    spell_lists: Res<Assets<SpellList>>,
+    mut combat_state: ResMut<State<CombatState>>,
+    mut log: EventWriter<CombatLogEvent>,
+) {
+    let (mut player_stats, spellbook) = player_query.single_mut();
+    let spells: Vec<&Spell> = match spell_lists.get(&spell_definitions.0) {
+        Some(spells) => spells.known(spellbook).collect(),
+        None => return,
+    };
+    let spell_count = spells.len() as isize;
+    if spell_count == 0 {
+        return;
+    }
+
+    let mut new_selection = spell_selection.selected as isize;
+    if keyboard.just_pressed(KeyCode::W) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        new_selection += 1;
+    }
+    spell_selection.selected = ((new_selection + spell_count) % spell_count) as usize;
+
+    // Line the cursor up with the selected row
+    for mut cursor_transform in cursor_query.iter_mut() {
+        cursor_transform.translation.y = list_menu_row_y(spells.len(), spell_selection.selected);
+    }
+
+    if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::Back]) {
+        combat_state.set(CombatState::PlayerTurn).unwrap();
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let spell = spells[spell_selection.selected];
+        if spell.mana_cost > player_stats.mana {
+            return;
+        }
+        match spell.target {
+            SpellTarget::SingleEnemy => {
+                target_state.action = PlayerAction::Cast(spell.clone());
+                combat_state.set(CombatState::PlayerTargeting).unwrap();
+                // Targeting runs in the same frame, don't let it see this press
+                keyboard.clear_just_pressed(KeyCode::Return);
+            }
+            SpellTarget::AllEnemies => {
+                let targets = living_enemies(&enemy_query);
+                cast_spell(
+                    spell,
+                    &targets,
+                    &mut player_stats,
+                    &mut fight_event_writer,
+                    CombatState::PlayerAttack,
+                    PLAYER_NAME,
+                    &mut log,
+                );
+            }
+            SpellTarget::Myself => {
+                cast_spell(
+                    spell,
+                    &[],
+                    &mut player_stats,
+                    &mut fight_event_writer,
+                    CombatState::PlayerAttack,
+                    PLAYER_NAME,
+                    &mut log,
+                );
+                combat_state.set(CombatState::PlayerAttack).unwrap();
+            }
+        }
+    }
+}
+
+fn spawn_item_menu(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    nine_slice_indices: Res<NineSliceIndices>,
+    mut item_selection: ResMut<ItemMenuSelection>,
+    player_query: Query<&Inventory, With<Player>>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+) {
+    let inventory = player_query.single();
+    // Equipment can't be used mid fight
+    let stacks = match item_lists.get(&item_definitions.0) {
+        Some(items) => inventory.usable_stacks(items),
+        None => return,
+    };
+    if item_selection.selected >= stacks.len() {
+        item_selection.selected = 0;
+    }
+
+    let name_width = stacks.iter().map(|stack| stack.name.len()).max().unwrap_or(0);
+    let lines: Vec<(String, Color)> = stacks
+        .iter()
+        .map(|stack| {
+            let line = format!("{:<width$} x{}", stack.name, stack.count, width = name_width);
+            (line, Color::rgb(0.8, 0.8, 0.8))
+        })
+        .collect();
+
+    let size = list_menu_size(&lines);
+    let center = Vec3::new(
+        RESOLUTION - size.x * TILE_SIZE / 2.0,
+        -1.0 + 3.0 * TILE_SIZE + size.y * TILE_SIZE / 2.0,
+        100.0,
+    );
+    let menu = spawn_list_menu(
+        &mut commands,
+        &ascii,
+        &nine_slice_indices,
+        &lines,
+        item_selection.selected,
+        center,
+    );
+    commands
+        .entity(menu)
+        .insert(Name::new("Item Menu"))
+        .insert(ItemMenu);
+}
+
+fn despawn_item_menu(mut commands: Commands, menu_query: Query<Entity, With<ItemMenu>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+fn item_menu_input(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    keyboard: Res<Input<KeyCode>>,
+    mut player_query: Query<(&mut Inventory, &mut CombatStats), With<Player>>,
+    mut cursor_query: Query<&mut Transform, With<ListMenuCursor>>,
+    mut item_selection: ResMut<ItemMenuSelection>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut combat_state: ResMut<State<CombatState>>,
+    mut log: EventWriter<CombatLogEvent>,
+) {
+    let (mut inventory, mut stats) = player_query.single_mut();
+    let items = match item_lists.get(&item_definitions.0) {
+        Some(items) => items,
+        None => return,
+    };
+    let usable: Vec<String> = inventory
+        .usable_stacks(items)
+        .iter()
+        .map(|stack| stack.name.clone())
+        .collect();
+    let stack_count = usable.len() as isize;
+    if stack_count == 0 {
+        return;
+    }
+
+    let mut new_selection = item_selection.selected as isize;
+    if keyboard.just_pressed(KeyCode::W) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        new_selection += 1;
+    }
+    item_selection.selected = ((new_selection + stack_count) % stack_count) as usize;
+
+    for mut cursor_transform in cursor_query.iter_mut() {
+        cursor_transform.translation.y =
+            list_menu_row_y(usable.len(), item_selection.selected);
+    }
+
+    if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::Back]) {
+        combat_state.set(CombatState::PlayerTurn).unwrap();
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let item = match items.get(&usable[item_selection.selected]) {
+            Some(item) => item,
+            None => return,
+        };
+        if let Some(message) = inventory.use_item(item, &mut stats) {
+            spawn_feedback_text(&mut commands, &ascii, &item.name, player_feedback_position());
+            log.send(CombatLogEvent(format!(
+                "{} {}. {}.",
+                conjugate(PLAYER_NAME, "use"),
+                item.name,
+                message
+            )));
+            // Using an item takes the turn
+            combat_state.set(CombatState::PlayerAttack).unwrap();
+        }
+    }
+}
+
+fn spawn_target_cursor(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    mut target_state: ResMut<CombatTargetSelection>,
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+) {
+    let living = living_enemies(&enemy_query);
+    if target_state.selected >= living.len() {
+        target_state.selected = 0;
+    }
+
+    let cursor = spawn_ascii_sprite(
+        &mut commands,
+        &ascii,
+        31,
+        Color::RED,
+        Vec3::new(0.0, 0.0, 150.0),
+        Vec3::splat(2.0),
+    );
+    commands
+        .entity(cursor)
+        .insert(TargetCursor)
+        .insert(Name::new("Target Cursor"));
+}
+
+fn despawn_target_cursor(mut commands: Commands, cursor_query: Query<Entity, With<TargetCursor>>) {
+    for cursor in cursor_query.iter() {
+        commands.entity(cursor).despawn_recursive();
+    }
+}
+
+fn target_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut fight_event_writer: EventWriter<FightEvent>,
+    mut player_query: Query<&mut CombatStats, (With<Player>, Without<Enemy>)>,
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+    mut cursor_query: Query<&mut Transform, (With<TargetCursor>, Without<Enemy>)>,
+    mut target_state: ResMut<CombatTargetSelection>,
+    mut combat_state: ResMut<State<CombatState>>,
+    mut rng: ResMut<GameRng>,
+    mut log: EventWriter<CombatLogEvent>,
+) {
+    let living = living_enemies(&enemy_query);
+    let living_count = living.len() as isize;
+    if living_count == 0 {
+        return;
+    }
+
+    let mut new_selection = target_state.selected as isize;
+    if keyboard.just_pressed(KeyCode::A) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::D) {
+        new_selection += 1;
+    }
+    target_state.selected = ((new_selection + living_count) % living_count) as usize;
+    let target = living[target_state.selected];
+
+    // Keep the cursor above the selected enemy
+    if let Ok((_, _, enemy_transform)) = enemy_query.get(target) {
+        for mut cursor_transform in cursor_query.iter_mut() {
+            cursor_transform.translation.x = enemy_transform.translation.x;
+            cursor_transform.translation.y = enemy_transform.translation.y + 0.65;
+        }
+    }
+
+    if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::Back]) {
+        combat_state.set(CombatState::PlayerTurn).unwrap();
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let mut player_stats = player_query.single_mut();
+        let (_, target_stats, _) = enemy_query.get(target).unwrap();
+        match &target_state.action {
+            PlayerAction::Attack => {
+                log.send(CombatLogEvent(format!("{}!", conjugate(PLAYER_NAME, "attack"))));
                 fight_event_writer.send(FightEvent {
                     target: target,
                     attack_type: AttackType::Standard,
                     damage_amount: player_stats.attack,
                     next_state: CombatState::PlayerAttack,
+                    vfx_index: None,
+                    status: None,
+                    outcome: HitOutcome::roll(&player_stats, target_stats, &mut rng),
                 });
             }
-            CombatMenuOption::MagicAttack => {
-                let (mut player_stats, player_children, player_entity) = player_query.single_mut();
-                let target = enemy_query.iter().next().unwrap();
-
-                if player_stats.mana > 0 {
-                    player_stats.mana -= 1;
-
-                    //Update mana
-                    for child in player_children.iter() {
-                        //See if this child is the health text
-                        if let Ok(transform) = mana_text.get(*child) {
-                            //Delete old text
-                            commands.entity(*child).despawn_recursive();
-                            //Create new text
-                            let new_mana_text = spawn_ascii_text(
-                                &mut commands,
-                                &ascii,
-                                &format!("Mana: {}", player_stats.mana as usize),
-                                //relative to enemy pos
-                                transform.translation,
-                            );
-                            commands.entity(new_mana_text).insert(CombatManaText);
-                            commands.entity(player_entity).add_child(new_mana_text);
-                        }
-                    }
-
-                    fight_event_writer.send(FightEvent {
-                        target: target,
-                        attack_type: AttackType::MagicGeneric,
-                        damage_amount: 4,
-                        next_state: CombatState::PlayerAttack,
-                    });
-                }
-            }
-            CombatMenuOption::Run => {
-                create_fadeout(&mut commands, None, &ascii);
+            PlayerAction::Cast(spell) => {
+                cast_spell(
+                    spell,
+                    &[target],
+                    &mut player_stats,
+                    &mut fight_event_writer,
+                    CombatState::PlayerAttack,
+                    PLAYER_NAME,
+                    &mut log,
+                );
             }
         }
     }
@@ -646,7 +1888,7 @@ fn despawn_system(
         despawn_timer.0.tick(time.delta());
 
         if despawn_timer.0.finished() {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
@@ -0,0 +1,156 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    combat::{Affinity, AttackType, CombatStats},
+    enemy_ai::AiProfile,
+    loot::LootTable,
+    status::StatusKind,
+};
+
+pub struct EnemiesPlugin;
+
+/// Stats an enemy starts every fight with
+#[derive(Deserialize, Clone)]
+pub struct EnemyStats {
+    pub health: isize,
+    #[serde(default)]
+    pub mana: isize,
+    pub attack: isize,
+    pub defense: isize,
+    pub speed: isize,
+    pub accuracy: isize,
+    pub evasion: isize,
+    pub crit_rate: isize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EnemyDefinition {
+    pub name: String,
+    pub stats: EnemyStats,
+    pub exp: usize,
+    #[serde(default)]
+    pub gold: usize,
+    // Indices into the characters sheet
+    pub frames: Vec<usize>,
+    #[serde(default = "default_frame_time")]
+    pub frame_time: f32,
+    pub size: f32,
+    // How often it shows up compared to the others
+    #[serde(default = "default_spawn_weight")]
+    pub spawn_weight: u32,
+    // Attack types missing from the list hit normally
+    #[serde(default)]
+    pub affinities: Vec<(AttackType, Affinity)>,
+    // Status its attacks can inflict and how likely it is
+    #[serde(default)]
+    pub attack_status: Option<(StatusKind, f32)>,
+    pub ai: AiProfile,
+    #[serde(default)]
+    pub loot: LootTable,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "b3e1c0a4-8f6d-4e2b-a957-1d0c6e4f3a28"]
+pub struct EnemyList {
+    pub enemies: Vec<EnemyDefinition>,
+}
+
+pub struct EnemyDefinitions(pub Handle<EnemyList>);
+
+#[derive(Default)]
+pub struct EnemyListLoader;
+
+fn default_frame_time() -> f32 {
+    0.2
+}
+
+fn default_spawn_weight() -> u32 {
+    1
+}
+
+impl EnemyStats {
+    pub fn to_combat_stats(&self) -> CombatStats {
+        CombatStats {
+            health: self.health,
+            max_health: self.health,
+            mana: self.mana,
+            max_mana: self.mana,
+            attack: self.attack,
+            defense: self.defense,
+            speed: self.speed,
+            accuracy: self.accuracy,
+            evasion: self.evasion,
+            crit_rate: self.crit_rate,
+        }
+    }
+}
+
+impl EnemyDefinition {
+    pub fn affinity(&self, attack_type: AttackType) -> Affinity {
+        self.affinities
+            .iter()
+            .find(|(affinity_type, _)| *affinity_type == attack_type)
+            .map_or(Affinity::Normal, |(_, affinity)| *affinity)
+    }
+}
+
+impl EnemyList {
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.enemies.iter().any(|enemy| enemy.name == name)
+    }
+
+    /// Picks an enemy weighted by spawn weight with a roll in 0..1,
+    /// only from the given names unless the list is empty
+    pub fn pick(&self, roll: f32, allowed: &[String]) -> Option<&EnemyDefinition> {
+        let candidates: Vec<&EnemyDefinition> = self
+            .enemies
+            .iter()
+            .filter(|enemy| allowed.is_empty() || allowed.contains(&enemy.name))
+            .collect();
+        let total: u32 = candidates.iter().map(|enemy| enemy.spawn_weight).sum();
+        let mut pick = (roll * total as f32) as u32;
+        for enemy in candidates.iter() {
+            if pick < enemy.spawn_weight {
+                return Some(enemy);
+            }
+            pick -= enemy.spawn_weight;
+        }
+        candidates.last().copied()
+    }
+}
+
+impl AssetLoader for EnemyListLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let enemy_list = ron::de::from_bytes::<EnemyList>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(enemy_list));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
+
+impl Plugin for EnemiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<EnemyList>()
+            .init_asset_loader::<EnemyListLoader>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_enemies);
+    }
+}
+
+fn load_enemies(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(EnemyDefinitions(assets.load("data/game.enemies.ron")));
+}
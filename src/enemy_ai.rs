@@ -0,0 +1,57 @@
+use serde::Deserialize;
+
+use crate::combat::CombatStats;
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub enum EnemyAction {
+    Attack,
+    // Name of a spell from the spell definitions
+    Cast(String),
+    Defend,
+    Flee,
+    Buff,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AiRule {
+    pub action: EnemyAction,
+    pub weight: u32,
+    /// Only considered once health drops to this fraction of max health
+    #[serde(default)]
+    pub below_health: Option<f32>,
+}
+
+/// Weighted actions an enemy picks from on its turn
+#[derive(Deserialize, Clone)]
+pub struct AiProfile {
+    pub rules: Vec<AiRule>,
+}
+
+impl AiProfile {
+    /// Picks an action with a roll in 0..1, `usable` rules out actions
+    /// the enemy can't afford right now
+    pub fn choose(
+        &self,
+        stats: &CombatStats,
+        roll: f32,
+        usable: impl Fn(&EnemyAction) -> bool,
+    ) -> EnemyAction {
+        let health = stats.health as f32 / stats.max_health.max(1) as f32;
+        let available: Vec<&AiRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.below_health.is_none_or(|fraction| health <= fraction))
+            .filter(|rule| usable(&rule.action))
+            .collect();
+
+        let total: u32 = available.iter().map(|rule| rule.weight).sum();
+        let mut pick = (roll * total as f32) as u32;
+        for rule in available {
+            if pick < rule.weight {
+                return rule.action.clone();
+            }
+            pick -= rule.weight;
+        }
+        EnemyAction::Attack
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::CombatStats,
+    items::{ItemDefinitions, ItemEffect, ItemList},
+};
+
+pub struct EquipmentPlugin;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum EquipSlot {
+    Weapon,
+    Armor,
+    Accessory,
+}
+
+/// A set of stat values, used both for base stats and for equipment bonuses
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct StatBlock {
+    pub max_health: isize,
+    pub max_mana: isize,
+    pub attack: isize,
+    pub defense: isize,
+    pub speed: isize,
+    pub accuracy: isize,
+    pub evasion: isize,
+    pub crit_rate: isize,
+}
+
+/// The player's stats without any equipment, only leveling changes these
+#[derive(Component, Clone, Copy)]
+pub struct BaseStats(pub StatBlock);
+
+#[derive(Component, Default, Clone, Serialize, Deserialize)]
+pub struct Equipment {
+    pub weapon: Option<String>,
+    pub armor: Option<String>,
+    pub accessory: Option<String>,
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 3] = [EquipSlot::Weapon, EquipSlot::Armor, EquipSlot::Accessory];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EquipSlot::Weapon => "Weapon",
+            EquipSlot::Armor => "Armor",
+            EquipSlot::Accessory => "Accessory",
+        }
+    }
+}
+
+impl StatBlock {
+    /// Names and values in the order they are shown on screen
+    pub fn entries(&self) -> [(&'static str, isize); 8] {
+        [
+            ("Max HP", self.max_health),
+            ("Max MP", self.max_mana),
+            ("Attack", self.attack),
+            ("Defense", self.defense),
+            ("Speed", self.speed),
+            ("Accuracy", self.accuracy),
+            ("Evasion", self.evasion),
+            ("Crit", self.crit_rate),
+        ]
+    }
+
+    pub fn with_bonus(mut self, bonus: &StatBlock) -> StatBlock {
+        self.max_health += bonus.max_health;
+        self.max_mana += bonus.max_mana;
+        self.attack += bonus.attack;
+        self.defense += bonus.defense;
+        self.speed += bonus.speed;
+        self.accuracy += bonus.accuracy;
+        self.evasion += bonus.evasion;
+        self.crit_rate += bonus.crit_rate;
+        self
+    }
+
+    /// Combat stats with these values, current health and mana are clamped to the new maxima
+    pub fn to_combat_stats(self, health: isize, mana: isize) -> CombatStats {
+        // Gear can push things negative, nothing useful comes of that
+        let max_health = self.max_health.max(1);
+        let max_mana = self.max_mana.max(0);
+        CombatStats {
+            health: health.min(max_health),
+            max_health: max_health,
+            mana: mana.min(max_mana),
+            max_mana: max_mana,
+            attack: self.attack.max(0),
+            defense: self.defense.max(0),
+            speed: self.speed.max(0),
+            accuracy: self.accuracy.max(0),
+            evasion: self.evasion.max(0),
+            crit_rate: self.crit_rate.max(0),
+        }
+    }
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipSlot) -> Option<&String> {
+        match slot {
+            EquipSlot::Weapon => self.weapon.as_ref(),
+            EquipSlot::Armor => self.armor.as_ref(),
+            EquipSlot::Accessory => self.accessory.as_ref(),
+        }
+    }
+
+    /// Puts an item in the slot and returns whatever was there before
+    pub fn set(&mut self, slot: EquipSlot, item: Option<String>) -> Option<String> {
+        let current = match slot {
+            EquipSlot::Weapon => &mut self.weapon,
+            EquipSlot::Armor => &mut self.armor,
+            EquipSlot::Accessory => &mut self.accessory,
+        };
+        std::mem::replace(current, item)
+    }
+
+    /// Base stats plus the bonus of everything equipped
+    pub fn apply(&self, base: &StatBlock, items: &ItemList) -> StatBlock {
+        EquipSlot::ALL
+            .iter()
+            .filter_map(|slot| self.get(*slot))
+            .filter_map(|name| items.get(name))
+            .fold(*base, |stats, item| match &item.effect {
+                ItemEffect::Equip(_, bonus) => stats.with_bonus(bonus),
+                _ => stats,
+            })
+    }
+}
+
+impl Plugin for EquipmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, update_equipped_stats);
+    }
+}
+
+/// Rebuilds combat stats from base stats and gear, so nothing is ever added twice
+fn update_equipped_stats(
+    mut stats_query: Query<(
+        ChangeTrackers<BaseStats>,
+        ChangeTrackers<Equipment>,
+        &BaseStats,
+        &Equipment,
+        &mut CombatStats,
+    )>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut item_events: EventReader<AssetEvent<ItemList>>,
+) {
+    // Bonuses can only be looked up once game.items.ron is in
+    let items_changed = item_events.iter().count() > 0;
+    let items = match item_lists.get(&item_definitions.0) {
+        Some(items) => items,
+        None => return,
+    };
+
+    for (base_tracker, equipment_tracker, base, equipment, mut stats) in stats_query.iter_mut() {
+        if !items_changed && !base_tracker.is_changed() && !equipment_tracker.is_changed() {
+            continue;
+        }
+        *stats = equipment
+            .apply(&base.0, items)
+            .to_combat_stats(stats.health, stats.mana);
+    }
+}
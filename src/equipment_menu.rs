@@ -0,0 +1,329 @@
+use bevy::{prelude::*, render::camera::Camera2d};
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_colored_ascii_text, spawn_list_menu, AsciiSheet, NineSliceIndices},
+    combat::CombatStats,
+    equipment::{BaseStats, EquipSlot, Equipment},
+    items::{Inventory, ItemDefinitions, ItemList},
+    player::Player,
+    GameState, TILE_SIZE,
+};
+
+pub struct EquipmentMenuPlugin;
+
+#[derive(Component)]
+pub struct EquipmentScreen;
+
+#[derive(Default)]
+pub struct EquipmentMenuState {
+    slot: usize,
+    // Picking something for the selected slot, the stats show a preview
+    choosing: bool,
+    choice: usize,
+    message: String,
+}
+
+impl Plugin for EquipmentMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EquipmentMenuState>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld).with_system(open_equipment_menu),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Equipment).with_system(reset_equipment_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Equipment)
+                    .with_system(equipment_menu_input)
+                    .with_system(refresh_equipment_screen.after(equipment_menu_input)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Equipment).with_system(despawn_equipment_screen),
+            );
+    }
+}
+
+/// Everything that could go in the slot, None takes off what's there
+fn candidates(
+    inventory: &Inventory,
+    items: &ItemList,
+    slot: EquipSlot,
+    equipment: &Equipment,
+) -> Vec<Option<String>> {
+    let mut candidates: Vec<Option<String>> = inventory
+        .stacks
+        .iter()
+        .filter(|stack| {
+            items
+                .get(&stack.name)
+                .is_some_and(|item| item.equip_slot() == Some(slot))
+        })
+        .map(|stack| Some(stack.name.clone()))
+        .collect();
+    if equipment.get(slot).is_some() {
+        candidates.push(None);
+    }
+    candidates
+}
+
+fn open_equipment_menu(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    player_query: Query<&Player>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::C) && player_query.single().active {
+        state.push(GameState::Equipment).unwrap();
+        keyboard.clear_just_pressed(KeyCode::C);
+    }
+}
+
+fn reset_equipment_menu(mut menu_state: ResMut<EquipmentMenuState>) {
+    *menu_state = EquipmentMenuState::default();
+}
+
+fn equipment_menu_input(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut player_query: Query<(&mut Inventory, &mut Equipment), With<Player>>,
+    mut menu_state: ResMut<EquipmentMenuState>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let items = match item_lists.get(&item_definitions.0) {
+        Some(items) => items,
+        None => return,
+    };
+    let (mut inventory, mut equipment) = player_query.single_mut();
+    let slot = EquipSlot::ALL[menu_state.slot];
+
+    let mut step = 0;
+    if keyboard.just_pressed(KeyCode::W) {
+        step -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        step += 1;
+    }
+
+    if !menu_state.choosing {
+        if keyboard.any_just_pressed([KeyCode::C, KeyCode::Escape]) {
+            state.pop().unwrap();
+            keyboard.clear_just_pressed(KeyCode::C);
+            return;
+        }
+        if step != 0 {
+            let slot_count = EquipSlot::ALL.len() as isize;
+            menu_state.slot = ((menu_state.slot as isize + step + slot_count) % slot_count) as usize;
+        }
+        if keyboard.just_pressed(KeyCode::Return) {
+            if candidates(&inventory, items, slot, &equipment).is_empty() {
+                menu_state.message = format!("Nothing to wear as {}", slot.label());
+            } else {
+                menu_state.choosing = true;
+                menu_state.choice = 0;
+                menu_state.message.clear();
+            }
+        }
+        return;
+    }
+
+    if keyboard.any_just_pressed([KeyCode::Escape, KeyCode::Back]) {
+        menu_state.choosing = false;
+        return;
+    }
+
+    let candidates = candidates(&inventory, items, slot, &equipment);
+    let candidate_count = candidates.len() as isize;
+    if step != 0 {
+        menu_state.choice =
+            ((menu_state.choice as isize + step + candidate_count) % candidate_count) as usize;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let choice = candidates[menu_state.choice].clone();
+        // Whatever comes off goes back in the bag, unless the bag is full
+        if let Some(previous) = equipment.get(slot).and_then(|name| items.get(name)) {
+            if inventory.add(previous, 1) == 0 {
+                menu_state.message = format!("No room for {}", previous.name);
+                return;
+            }
+        }
+        if let Some(name) = &choice {
+            inventory.remove_one(name);
+        }
+        let previous = equipment.set(slot, choice.clone());
+        menu_state.message = match (choice, previous) {
+            (Some(name), _) => format!("Equipped {}", name),
+            (None, Some(name)) => format!("Took off {}", name),
+            (None, None) => String::new(),
+        };
+        menu_state.choosing = false;
+    }
+}
+
+fn refresh_equipment_screen(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    menu_state: Res<EquipmentMenuState>,
+    player_query: Query<
+        (
+            ChangeTrackers<Inventory>,
+            ChangeTrackers<Equipment>,
+            ChangeTrackers<CombatStats>,
+            &Inventory,
+            &Equipment,
+            &BaseStats,
+            &CombatStats,
+        ),
+        With<Player>,
+    >,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    screen_query: Query<Entity, With<EquipmentScreen>>,
+) {
+    let (inventory_tracker, equipment_tracker, stats_tracker, inventory, equipment, base, stats) =
+        player_query.single();
+    if !menu_state.is_changed()
+        && !inventory_tracker.is_changed()
+        && !equipment_tracker.is_changed()
+        && !stats_tracker.is_changed()
+    {
+        return;
+    }
+    let items = match item_lists.get(&item_definitions.0) {
+        Some(items) => items,
+        None => return,
+    };
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let center = camera_query.single().translation.truncate().extend(100.0);
+    let mut entities = Vec::new();
+
+    let stats_text = format!(
+        "Health: {}/{}  Mana: {}/{}",
+        stats.health, stats.max_health, stats.mana, stats.max_mana
+    );
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &stats_text,
+        center + Vec3::new(-((stats_text.len() / 2) as f32 * TILE_SIZE), 0.8, 0.0),
+    ));
+
+    let slot_lines: Vec<(String, Color)> = EquipSlot::ALL
+        .iter()
+        .map(|slot| {
+            let worn = equipment.get(*slot).map_or("-", |name| name.as_str());
+            (format!("{:<10}{:<12}", slot.label(), worn), Color::rgb(0.8, 0.8, 0.8))
+        })
+        .collect();
+    entities.push(spawn_list_menu(
+        &mut commands,
+        &ascii,
+        &indices,
+        &slot_lines,
+        menu_state.slot,
+        center + Vec3::new(-0.75, 0.35, 0.0),
+    ));
+
+    let slot = EquipSlot::ALL[menu_state.slot];
+    let current = equipment.apply(&base.0, items);
+    let mut preview = current;
+    if menu_state.choosing {
+        let candidates = candidates(inventory, items, slot, equipment);
+        let name_width = candidates.iter().flatten().map(|name| name.len()).max().unwrap_or(0);
+        let candidate_lines: Vec<(String, Color)> = candidates
+            .iter()
+            .map(|candidate| {
+                let line = match candidate {
+                    Some(name) => {
+                        format!("{:<width$} x{}", name, inventory.count(name), width = name_width)
+                    }
+                    None => "Take off".to_string(),
+                };
+                (line, Color::rgb(0.8, 0.8, 0.8))
+            })
+            .collect();
+        entities.push(spawn_list_menu(
+            &mut commands,
+            &ascii,
+            &indices,
+            &candidate_lines,
+            menu_state.choice,
+            center + Vec3::new(-0.75, -0.25, 0.0),
+        ));
+
+        let choice = candidates.get(menu_state.choice).cloned().flatten();
+        if let Some(item) = choice.as_ref().and_then(|name| items.get(name)) {
+            entities.push(spawn_ascii_text(
+                &mut commands,
+                &ascii,
+                &item.description,
+                center + Vec3::new(-((item.description.len() / 2) as f32 * TILE_SIZE), -0.6, 0.0),
+            ));
+        }
+        let mut previewed = equipment.clone();
+        previewed.set(slot, choice);
+        preview = previewed.apply(&base.0, items);
+    }
+
+    // Current stats, with what they'd become next to them while picking
+    for (i, ((label, now), (_, after))) in current
+        .entries()
+        .iter()
+        .zip(preview.entries().iter())
+        .enumerate()
+    {
+        let (line, color) = if menu_state.choosing {
+            let color = if after > now {
+                Color::rgb(0.3, 0.9, 0.3)
+            } else if after < now {
+                Color::rgb(0.9, 0.3, 0.3)
+            } else {
+                Color::rgb(0.8, 0.8, 0.8)
+            };
+            (format!("{:<9}{:>3} > {:>3}", label, now, after), color)
+        } else {
+            (format!("{:<9}{:>3}", label, now), Color::rgb(0.8, 0.8, 0.8))
+        };
+        entities.push(spawn_colored_ascii_text(
+            &mut commands,
+            &ascii,
+            &line,
+            center + Vec3::new(0.35, 0.6 - i as f32 * TILE_SIZE, 0.0),
+            color,
+        ));
+    }
+
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &menu_state.message,
+        center + Vec3::new(-((menu_state.message.len() / 2) as f32 * TILE_SIZE), -0.7, 0.0),
+    ));
+
+    let hint_text = if menu_state.choosing {
+        "Enter: equip  Esc: back"
+    } else {
+        "Enter: change  C: close"
+    };
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        hint_text,
+        center + Vec3::new(-((hint_text.len() / 2) as f32 * TILE_SIZE), -0.85, 0.0),
+    ));
+
+    for entity in entities {
+        commands.entity(entity).insert(EquipmentScreen);
+    }
+}
+
+fn despawn_equipment_screen(mut commands: Commands, screen_query: Query<Entity, With<EquipmentScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -4,11 +4,20 @@ use crate::{ascii::AsciiSheet, GameState};
 
 pub struct FadeoutPlugin;
 
+/// State stack operation performed when the screen is fully faded
+#[derive(Clone, Copy)]
+pub enum FadeTransition {
+    Push(GameState),
+    Pop,
+    Set(GameState),
+    Replace(GameState),
+}
+
 #[derive(Component)]
 struct ScreenFade {
     alpha: f32,
     sent: bool,
-    next_state: Option<GameState>,
+    transition: FadeTransition,
     timer: Timer,
 }
 
@@ -51,10 +60,11 @@ fn fadeout(
         sprite.color.set_a(fade.alpha);
 
         if fade.timer.percent() > 0.5 && !fade.sent {
-            if let Some(next_state) = fade.next_state {
-                state.push(next_state).unwrap();
-            } else {
-                state.pop().unwrap();
+            match fade.transition {
+                FadeTransition::Push(next_state) => state.push(next_state).unwrap(),
+                FadeTransition::Pop => state.pop().unwrap(),
+                FadeTransition::Set(next_state) => state.set(next_state).unwrap(),
+                FadeTransition::Replace(next_state) => state.replace(next_state).unwrap(),
             }
             fade.sent = true;
         }
@@ -69,6 +79,18 @@ pub fn create_fadeout(
     commands: &mut Commands,
     next_state: Option<GameState>,
     ascii: &Res<AsciiSheet>,
+) {
+    let transition = match next_state {
+        Some(next_state) => FadeTransition::Push(next_state),
+        None => FadeTransition::Pop,
+    };
+    create_transition_fadeout(commands, transition, ascii);
+}
+
+pub fn create_transition_fadeout(
+    commands: &mut Commands,
+    transition: FadeTransition,
+    ascii: &Res<AsciiSheet>,
 ) {
     let mut sprite = TextureAtlasSprite::new(0);
     sprite.color = Color::rgba(0.1, 0.1, 0.15, 0.0);
@@ -87,7 +109,7 @@ pub fn create_fadeout(
         .insert(ScreenFade {
             alpha: 0.0,
             sent: false,
-            next_state: next_state,
+            transition: transition,
             timer: Timer::from_seconds(1.0, false),
         })
         .insert(Name::new("Fadeout"));
@@ -0,0 +1,248 @@
+use bevy::{prelude::*, render::camera::Camera2d};
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_nine_slice, AsciiSheet, NineSlice, NineSliceIndices},
+    combat::CombatStats,
+    fadeout::{create_transition_fadeout, FadeTransition},
+    player::Player,
+    rng::GameRng,
+    save::{any_save_exists, begin_load, read_latest_save, PendingLoad},
+    tilemap::{PendingWarp, WarpDestination},
+    GameState, TILE_SIZE,
+};
+
+pub struct GameOverPlugin;
+
+#[derive(Component)]
+pub struct GameOverScreen;
+
+#[derive(Component, PartialEq, Eq, Clone, Copy)]
+pub enum GameOverOption {
+    Revive,
+    LoadSave,
+    Title,
+}
+
+pub struct GameOverSelection {
+    selected: GameOverOption,
+    // Left to right, loading is only offered with a save around
+    options: Vec<GameOverOption>,
+    chosen: bool,
+}
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameOverSelection {
+            selected: GameOverOption::Revive,
+            options: Vec::new(),
+            chosen: false,
+        })
+        .add_system_set(
+            SystemSet::on_enter(GameState::GameOver).with_system(spawn_game_over_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::GameOver)
+                .with_system(game_over_input)
+                .with_system(highlight_game_over_buttons),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::GameOver).with_system(despawn_game_over_screen),
+        );
+    }
+}
+
+fn spawn_game_over_button(
+    commands: &mut Commands,
+    ascii: &AsciiSheet,
+    indices: &NineSliceIndices,
+    translation: Vec3,
+    text: &str,
+    id: GameOverOption,
+) -> Entity {
+    let width = (text.len() + 2) as f32;
+    let nine_slice = spawn_nine_slice(commands, ascii, indices, width, 3.0);
+
+    let x_offset = (-width / 2.0 + 1.5) * TILE_SIZE;
+    let text = spawn_ascii_text(commands, ascii, text, Vec3::new(x_offset, 0.0, 0.0));
+
+    commands
+        .spawn()
+        .insert(Transform {
+            translation: translation,
+            ..Default::default()
+        })
+        .insert(GlobalTransform::default())
+        .insert(Name::new("Button"))
+        .insert(id)
+        .add_child(nine_slice)
+        .add_child(text)
+        .id()
+}
+
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    mut selection: ResMut<GameOverSelection>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+) {
+    selection.selected = GameOverOption::Revive;
+    selection.chosen = false;
+    selection.options = if any_save_exists() {
+        vec![GameOverOption::Revive, GameOverOption::LoadSave, GameOverOption::Title]
+    } else {
+        vec![GameOverOption::Revive, GameOverOption::Title]
+    };
+
+    let center = camera_query.single().translation.truncate().extend(100.0);
+
+    let title_text = "You have been defeated";
+    let title = spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        title_text,
+        center + Vec3::new(-((title_text.len() / 2) as f32 * TILE_SIZE), 0.4, 0.0),
+    );
+    let mut entities = vec![title];
+
+    // Two lines, it doesn't fit across the screen in one
+    let penalty_lines = ["Revive at the last healer", "and lose half your exp and gold"];
+    for (i, penalty_text) in penalty_lines.iter().enumerate() {
+        entities.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            penalty_text,
+            center
+                + Vec3::new(
+                    -((penalty_text.len() / 2) as f32 * TILE_SIZE),
+                    0.2 - i as f32 * 1.25 * TILE_SIZE,
+                    0.0,
+                ),
+        ));
+    }
+
+    // The wide load button sits in the middle when it's there
+    let spacing = if selection.options.len() > 2 { 1.0 } else { 0.8 };
+    let left = -spacing * (selection.options.len() - 1) as f32 / 2.0;
+    for (i, option) in selection.options.iter().enumerate() {
+        let text = match option {
+            GameOverOption::Revive => "Revive",
+            GameOverOption::LoadSave => "Load last save",
+            GameOverOption::Title => "Title",
+        };
+        entities.push(spawn_game_over_button(
+            &mut commands,
+            &ascii,
+            &indices,
+            center + Vec3::new(left + spacing * i as f32, -0.2, 0.0),
+            text,
+            *option,
+        ));
+    }
+
+    for entity in entities {
+        commands.entity(entity).insert(GameOverScreen);
+    }
+}
+
+fn despawn_game_over_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<GameOverScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn highlight_game_over_buttons(
+    selection: Res<GameOverSelection>,
+    button_query: Query<(&Children, &GameOverOption)>,
+    nine_slice_query: Query<&Children, With<NineSlice>>,
+    mut sprites_query: Query<&mut TextureAtlasSprite>,
+) {
+    for (button_children, button_id) in button_query.iter() {
+        for button_child in button_children.iter() {
+            if let Ok(nine_slice_children) = nine_slice_query.get(*button_child) {
+                for nine_slice_child in nine_slice_children.iter() {
+                    if let Ok(mut sprite) = sprites_query.get_mut(*nine_slice_child) {
+                        if selection.selected == *button_id {
+                            sprite.color = Color::RED;
+                        } else {
+                            sprite.color = Color::WHITE;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn game_over_input(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    ascii: Res<AsciiSheet>,
+    mut selection: ResMut<GameOverSelection>,
+    mut player_query: Query<(&mut Player, &mut CombatStats)>,
+    mut pending_warp: ResMut<PendingWarp>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut rng: ResMut<GameRng>,
+) {
+    if selection.chosen {
+        return;
+    }
+
+    let count = selection.options.len();
+    let current = selection
+        .options
+        .iter()
+        .position(|option| *option == selection.selected)
+        .unwrap_or(0);
+    if keyboard.just_pressed(KeyCode::A) {
+        selection.selected = selection.options[(current + count - 1) % count];
+    }
+    if keyboard.just_pressed(KeyCode::D) {
+        selection.selected = selection.options[(current + 1) % count];
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        selection.chosen = true;
+        match selection.selected {
+            GameOverOption::Revive => {
+                // Penalty for dying, wake up at the last healer with half the exp and gold
+                let (mut player, mut stats) = player_query.single_mut();
+                player.exp /= 2;
+                player.gold /= 2;
+                stats.health = stats.max_health;
+                stats.mana = stats.max_mana;
+                // The healer can be on another map, the Warp state takes care of it
+                pending_warp.0 = Some(WarpDestination {
+                    map: player.respawn_map.clone(),
+                    position: player.respawn_point,
+                });
+                create_transition_fadeout(&mut commands, FadeTransition::Set(GameState::Warp), &ascii);
+            }
+            GameOverOption::LoadSave => match read_latest_save() {
+                Some(save) => {
+                    begin_load(&mut commands, save, &mut pending_load, &mut rng);
+                    // Same as loading from the save slots screen, the overworld is built again
+                    create_transition_fadeout(
+                        &mut commands,
+                        FadeTransition::Replace(GameState::Overworld),
+                        &ascii,
+                    );
+                }
+                None => {
+                    error!("No save could be read to load");
+                    selection.chosen = false;
+                }
+            },
+            GameOverOption::Title => {
+                create_transition_fadeout(
+                    &mut commands,
+                    FadeTransition::Replace(GameState::StartMenu),
+                    &ascii,
+                );
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use::bevy::prelude::*;
 
-use crate::{combat::EnemyType, TILE_SIZE};
+use crate::{enemies::EnemyDefinition, TILE_SIZE};
 
 pub struct GraphicsPlugin;
 
@@ -11,17 +11,13 @@ pub struct CharacterSheet {
     pub player_left: [usize; 3],
     pub player_right: [usize; 3],
 
-    pub bat_frames: [usize; 3],
-    pub ghost_frames: [usize; 3],
-
     pub healer: usize,
+    pub merchant: usize,
 }
 
+/// Map files pick tiles by index, the sheet is 32 tiles wide
 pub struct GroundTilesSheet {
     pub handle: Handle<TextureAtlas>,
-    pub sand: usize,
-    pub grass: usize,
-    pub wall: usize,
 }
 
 pub struct WorldObjectsSheet {
@@ -67,21 +63,14 @@ pub fn spawn_enemy_sprite(
     commands: &mut Commands,
     characters: &CharacterSheet,
     translation: Vec3,
-    enemy_type: EnemyType
+    definition: &EnemyDefinition
 ) -> Entity {
-    let mut sprite = TextureAtlasSprite::new(characters.bat_frames[0]);
-    sprite.custom_size = Some(Vec2::splat(0.5));
-    let animation = match enemy_type {
-        EnemyType::Bat => FrameAnimation {
-            timer: Timer::from_seconds(0.2, true),
-            frames: characters.bat_frames.to_vec(),
-            current_frame: 0,
-        },
-        EnemyType::Ghost => FrameAnimation {
-            timer: Timer::from_seconds(0.2, true),
-            frames: characters.ghost_frames.to_vec(),
-            current_frame: 0,
-        },
+    let mut sprite = TextureAtlasSprite::new(definition.frames.first().copied().unwrap_or(0));
+    sprite.custom_size = Some(Vec2::splat(definition.size));
+    let animation = FrameAnimation {
+        timer: Timer::from_seconds(definition.frame_time, true),
+        frames: definition.frames.clone(),
+        current_frame: 0,
     };
 
     commands.spawn_bundle(SpriteSheetBundle {
@@ -190,11 +179,9 @@ impl GraphicsPlugin {
             player_left: [characters_columns * 1 + 3, characters_columns * 1 + 4, characters_columns * 1 + 5],
             player_right: [characters_columns * 2 + 3, characters_columns * 2 + 4, characters_columns * 2 + 5],
             player_up: [characters_columns * 3 + 3, characters_columns * 3 + 4, characters_columns * 3 + 5],
-            
-            bat_frames: [characters_columns * 4 + 3, characters_columns * 4 + 4, characters_columns * 4 + 5],
-            ghost_frames: [characters_columns * 4 + 6, characters_columns * 4 + 7, characters_columns * 4 + 8],
 
             healer: characters_columns * 0 + 6,
+            merchant: characters_columns * 0 + 1,
         });
 
         // Ground tiles sheet
@@ -204,13 +191,8 @@ impl GraphicsPlugin {
         );
         let ground_tiles_atlas_handle = texture_atlases.add(ground_tiles_atlas);
 
-        let ground_tiles_columns = 32;
-
         commands.insert_resource(GroundTilesSheet {
             handle: ground_tiles_atlas_handle,
-            grass: ground_tiles_columns * 15 + 2,
-            sand: ground_tiles_columns * 6 + 2,
-            wall: ground_tiles_columns * 1 + 0,
         });
 
         // World objects sheet
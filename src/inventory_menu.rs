@@ -0,0 +1,206 @@
+use bevy::{prelude::*, render::camera::Camera2d};
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_list_menu, AsciiSheet, NineSliceIndices},
+    combat::CombatStats,
+    items::{Inventory, ItemDefinitions, ItemList},
+    player::Player,
+    GameState, TILE_SIZE,
+};
+
+pub struct InventoryMenuPlugin;
+
+#[derive(Component)]
+pub struct InventoryScreen;
+
+#[derive(Default)]
+pub struct InventoryMenuState {
+    selected: usize,
+    // Result of the last item used
+    message: String,
+}
+
+impl Plugin for InventoryMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InventoryMenuState>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld).with_system(open_inventory_menu),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Inventory).with_system(reset_inventory_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Inventory)
+                    .with_system(inventory_menu_input)
+                    .with_system(refresh_inventory_screen.after(inventory_menu_input)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Inventory).with_system(despawn_inventory_screen),
+            );
+    }
+}
+
+fn open_inventory_menu(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    player_query: Query<&Player>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // Not while talking or fading into a fight
+    if keyboard.just_pressed(KeyCode::I) && player_query.single().active {
+        state.push(GameState::Inventory).unwrap();
+        keyboard.clear_just_pressed(KeyCode::I);
+    }
+}
+
+fn reset_inventory_menu(mut menu_state: ResMut<InventoryMenuState>) {
+    // Always counts as changed here, so the screen gets built
+    menu_state.selected = 0;
+    menu_state.message.clear();
+}
+
+fn inventory_menu_input(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut player_query: Query<(&mut Inventory, &mut CombatStats), With<Player>>,
+    mut menu_state: ResMut<InventoryMenuState>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keyboard.any_just_pressed([KeyCode::I, KeyCode::Escape]) {
+        state.pop().unwrap();
+        keyboard.clear_just_pressed(KeyCode::I);
+        return;
+    }
+
+    let (mut inventory, mut stats) = player_query.single_mut();
+    let stack_count = inventory.stacks.len() as isize;
+    if stack_count == 0 {
+        return;
+    }
+
+    let mut new_selection = menu_state.selected as isize;
+    if keyboard.just_pressed(KeyCode::W) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        new_selection += 1;
+    }
+    let new_selection = ((new_selection + stack_count) % stack_count) as usize;
+    if new_selection != menu_state.selected {
+        menu_state.selected = new_selection;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let item = item_lists
+            .get(&item_definitions.0)
+            .and_then(|items| items.get(&inventory.stacks[menu_state.selected].name));
+        if item.is_some_and(|item| !item.is_usable()) {
+            menu_state.message = "Equip it with C".to_string();
+        } else if let Some(message) = item.and_then(|item| inventory.use_item(item, &mut stats)) {
+            menu_state.message = message;
+            // The last of a stack is gone, keep the cursor in the list
+            let stack_count = inventory.stacks.len();
+            menu_state.selected = menu_state.selected.min(stack_count.saturating_sub(1));
+        }
+    }
+}
+
+fn refresh_inventory_screen(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    menu_state: Res<InventoryMenuState>,
+    player_query: Query<(ChangeTrackers<Inventory>, &Inventory, &CombatStats, &Player)>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    screen_query: Query<Entity, With<InventoryScreen>>,
+) {
+    let (inventory_tracker, inventory, stats, player) = player_query.single();
+    if !menu_state.is_changed() && !inventory_tracker.is_changed() {
+        return;
+    }
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let center = camera_query.single().translation.truncate().extend(100.0);
+    let mut entities = Vec::new();
+
+    let stats_text = format!(
+        "Health: {}/{}  Mana: {}/{}  Gold: {}",
+        stats.health, stats.max_health, stats.mana, stats.max_mana, player.gold
+    );
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &stats_text,
+        center + Vec3::new(-((stats_text.len() / 2) as f32 * TILE_SIZE), 0.6, 0.0),
+    ));
+
+    if inventory.stacks.is_empty() {
+        let empty_text = "No items";
+        entities.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            empty_text,
+            center + Vec3::new(-((empty_text.len() / 2) as f32 * TILE_SIZE), 0.0, 0.0),
+        ));
+    } else {
+        let name_width = inventory.stacks.iter().map(|stack| stack.name.len()).max().unwrap_or(0);
+        let lines: Vec<(String, Color)> = inventory
+            .stacks
+            .iter()
+            .map(|stack| {
+                let line = format!("{:<width$} x{}", stack.name, stack.count, width = name_width);
+                (line, Color::rgb(0.8, 0.8, 0.8))
+            })
+            .collect();
+        entities.push(spawn_list_menu(
+            &mut commands,
+            &ascii,
+            &indices,
+            &lines,
+            menu_state.selected,
+            center + Vec3::new(0.0, 0.1, 0.0),
+        ));
+
+        let description = item_lists
+            .get(&item_definitions.0)
+            .and_then(|items| items.get(&inventory.stacks[menu_state.selected].name))
+            .map_or(String::new(), |item| {
+                format!("{} (carry {})", item.description, item.max_stack)
+            });
+        entities.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            &description,
+            center + Vec3::new(-((description.len() / 2) as f32 * TILE_SIZE), -0.45, 0.0),
+        ));
+    }
+
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &menu_state.message,
+        center + Vec3::new(-((menu_state.message.len() / 2) as f32 * TILE_SIZE), -0.6, 0.0),
+    ));
+
+    let hint_text = "Enter: use  I: close";
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        hint_text,
+        center + Vec3::new(-((hint_text.len() / 2) as f32 * TILE_SIZE), -0.85, 0.0),
+    ));
+
+    for entity in entities {
+        commands.entity(entity).insert(InventoryScreen);
+    }
+}
+
+fn despawn_inventory_screen(mut commands: Commands, screen_query: Query<Entity, With<InventoryScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
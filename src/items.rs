@@ -0,0 +1,180 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::CombatStats,
+    equipment::{EquipSlot, StatBlock},
+};
+
+pub struct ItemsPlugin;
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ItemEffect {
+    Heal(isize),
+    RestoreMana(isize),
+    // Can't be used, only worn in the slot
+    Equip(EquipSlot, StatBlock),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Item {
+    pub name: String,
+    pub description: String,
+    pub effect: ItemEffect,
+    pub max_stack: usize,
+    // Shops sell for this and buy back for half, 0 means it can't be sold
+    #[serde(default)]
+    pub price: usize,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "9c4d2e71-6b0a-4f38-8e15-c7a9d3b2f604"]
+pub struct ItemList {
+    pub items: Vec<Item>,
+}
+
+pub struct ItemDefinitions(pub Handle<ItemList>);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Items the player carries, stacks are kept in the order they were picked up
+#[derive(Component, Default)]
+pub struct Inventory {
+    pub stacks: Vec<ItemStack>,
+}
+
+#[derive(Default)]
+pub struct ItemListLoader;
+
+impl ItemEffect {
+    /// Applies the effect and returns a message for the player, None for equipment
+    pub fn apply(self, stats: &mut CombatStats) -> Option<String> {
+        match self {
+            ItemEffect::Heal(amount) => {
+                let healed = (stats.health + amount).min(stats.max_health) - stats.health;
+                stats.health += healed;
+                Some(format!("Recovered {} health", healed))
+            }
+            ItemEffect::RestoreMana(amount) => {
+                let restored = (stats.mana + amount).min(stats.max_mana) - stats.mana;
+                stats.mana += restored;
+                Some(format!("Recovered {} mana", restored))
+            }
+            ItemEffect::Equip(..) => None,
+        }
+    }
+}
+
+impl Item {
+    pub fn sell_price(&self) -> usize {
+        self.price / 2
+    }
+
+    pub fn is_usable(&self) -> bool {
+        !matches!(self.effect, ItemEffect::Equip(..))
+    }
+
+    pub fn equip_slot(&self) -> Option<EquipSlot> {
+        match self.effect {
+            ItemEffect::Equip(slot, _) => Some(slot),
+            _ => None,
+        }
+    }
+}
+
+impl ItemList {
+    pub fn get(&self, name: &str) -> Option<&Item> {
+        self.items.iter().find(|item| item.name == name)
+    }
+}
+
+impl Inventory {
+    /// Adds up to count items, returns how many fit under max_stack
+    pub fn add(&mut self, item: &Item, count: usize) -> usize {
+        let index = match self.stacks.iter().position(|stack| stack.name == item.name) {
+            Some(index) => index,
+            None => {
+                self.stacks.push(ItemStack { name: item.name.clone(), count: 0 });
+                self.stacks.len() - 1
+            }
+        };
+        let stack = &mut self.stacks[index];
+        let added = count.min(item.max_stack.saturating_sub(stack.count));
+        stack.count += added;
+        self.stacks.retain(|stack| stack.count > 0);
+        added
+    }
+
+    /// Stacks that can be used from a menu, equipment is left out
+    pub fn usable_stacks<'a>(&'a self, items: &ItemList) -> Vec<&'a ItemStack> {
+        self.stacks
+            .iter()
+            .filter(|stack| items.get(&stack.name).is_some_and(|item| item.is_usable()))
+            .collect()
+    }
+
+    pub fn count(&self, name: &str) -> usize {
+        self.stacks
+            .iter()
+            .find(|stack| stack.name == name)
+            .map_or(0, |stack| stack.count)
+    }
+
+    /// Takes one item out, empty stacks are dropped
+    pub fn remove_one(&mut self, name: &str) -> bool {
+        let stack = match self.stacks.iter_mut().find(|stack| stack.name == name) {
+            Some(stack) => stack,
+            None => return false,
+        };
+        stack.count -= 1;
+        self.stacks.retain(|stack| stack.count > 0);
+        true
+    }
+
+    /// Uses one item on the given stats, None if there's none left or it can't be used
+    pub fn use_item(&mut self, item: &Item, stats: &mut CombatStats) -> Option<String> {
+        if !item.is_usable() || !self.remove_one(&item.name) {
+            return None;
+        }
+        item.effect.apply(stats)
+    }
+}
+
+impl AssetLoader for ItemListLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let item_list = ron::de::from_bytes::<ItemList>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(item_list));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ItemList>()
+            .init_asset_loader::<ItemListLoader>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_items);
+    }
+}
+
+fn load_items(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(ItemDefinitions(assets.load("data/game.items.ron")));
+}
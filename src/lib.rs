@@ -0,0 +1,5 @@
+//! Map files, shared by the game and the dreadblaze-mapcheck tool
+#![allow(clippy::redundant_field_names)]
+
+pub mod map_format;
+mod tiled;
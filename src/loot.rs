@@ -0,0 +1,53 @@
+use serde::Deserialize;
+
+use crate::rng::GameRng;
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LootDrop {
+    // Name of an item from the item definitions
+    pub item: String,
+    pub weight: u32,
+    /// How many drop, picked evenly from min to max
+    #[serde(default = "default_count")]
+    pub min: usize,
+    #[serde(default = "default_count")]
+    pub max: usize,
+}
+
+/// What an enemy can leave behind, at most one drop per enemy
+#[derive(Deserialize, Clone, Default)]
+pub struct LootTable {
+    /// Chance that anything drops at all
+    pub chance: f32,
+    pub drops: Vec<LootDrop>,
+}
+
+impl LootTable {
+    /// Item name and count, None when nothing dropped
+    pub fn roll(&self, rng: &mut GameRng) -> Option<(String, usize)> {
+        if self.drops.is_empty() || !rng.chance(self.chance) {
+            return None;
+        }
+
+        let total: u32 = self.drops.iter().map(|drop| drop.weight).sum();
+        let mut pick = (rng.roll() * total as f32) as u32;
+        let mut chosen = self.drops.last()?;
+        for drop in self.drops.iter() {
+            if pick < drop.weight {
+                chosen = drop;
+                break;
+            }
+            pick -= drop.weight;
+        }
+
+        let count = rng.range(chosen.min..chosen.max.max(chosen.min) + 1);
+        if count == 0 {
+            return None;
+        }
+        Some((chosen.item.clone(), count))
+    }
+}
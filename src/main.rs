@@ -1,5 +1,5 @@
 #![allow(clippy::redundant_field_names)]
-use bevy::{prelude::*, render::camera::ScalingMode, window::PresentMode};
+use bevy::{asset::AssetServerSettings, prelude::*, render::camera::ScalingMode, window::PresentMode};
 
 pub const CLEAR: Color = Color::rgb(0.1, 0.1, 0.1);
 pub const RESOLUTION: f32 = 16.0 / 9.0;
@@ -15,6 +15,23 @@ mod audio;
 mod graphics;
 mod start_menu;
 mod npc;
+mod game_over;
+mod spells;
+mod status;
+mod enemy_ai;
+mod loot;
+mod rng;
+mod enemies;
+mod battle_log;
+mod items;
+mod inventory_menu;
+mod equipment;
+mod equipment_menu;
+mod progression;
+mod shops;
+mod shop_menu;
+mod save;
+mod save_menu;
 
 use graphics::GraphicsPlugin;
 use ascii::AsciiPlugin;
@@ -26,12 +43,33 @@ use tilemap::TileMapPlugin;
 use audio::GameAudioPlugin;
 use start_menu::MainMenuPlugin;
 use npc::NpcPlugin;
+use game_over::GameOverPlugin;
+use spells::SpellsPlugin;
+use rng::RngPlugin;
+use enemies::EnemiesPlugin;
+use battle_log::BattleLogPlugin;
+use items::ItemsPlugin;
+use inventory_menu::InventoryMenuPlugin;
+use equipment::EquipmentPlugin;
+use equipment_menu::EquipmentMenuPlugin;
+use progression::ProgressionPlugin;
+use shops::ShopsPlugin;
+use shop_menu::ShopMenuPlugin;
+use save::SavePlugin;
+use save_menu::SaveMenuPlugin;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum GameState {
     StartMenu,
     Overworld,
     Combat,
+    GameOver,
+    Inventory,
+    Equipment,
+    Shop,
+    SaveSlots,
+    // Behind a fade while the player moves to another map
+    Warp,
 }
 
 fn main() {
@@ -46,6 +84,11 @@ fn main() {
             resizable: false,
             ..Default::default()
         })
+        // Edited maps and data files get picked up while the game runs
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
+            ..default()
+        })
         .add_startup_system(spawn_camera)
         .add_plugins(DefaultPlugins)
         .add_plugin(PlayerPlugin)
@@ -58,6 +101,20 @@ fn main() {
         .add_plugin(GraphicsPlugin)
         .add_plugin(MainMenuPlugin)
         .add_plugin(NpcPlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(SpellsPlugin)
+        .add_plugin(RngPlugin)
+        .add_plugin(EnemiesPlugin)
+        .add_plugin(BattleLogPlugin)
+        .add_plugin(ItemsPlugin)
+        .add_plugin(InventoryMenuPlugin)
+        .add_plugin(EquipmentPlugin)
+        .add_plugin(EquipmentMenuPlugin)
+        .add_plugin(ProgressionPlugin)
+        .add_plugin(ShopsPlugin)
+        .add_plugin(ShopMenuPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(SaveMenuPlugin)
         .run();
 }
 
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::tiled::parse_tiled_map;
+
+// Line between the RON header and the tile grid
+pub const GRID_SEPARATOR: &str = "---";
+
+fn default_encounter_chance() -> f32 {
+    0.5
+}
+
+fn default_encounter_interval() -> f32 {
+    0.5
+}
+
+fn default_can_run() -> bool {
+    true
+}
+
+/// How often walking through encounter tiles starts a fight, and against what
+#[derive(Deserialize, Clone)]
+pub struct EncounterSettings {
+    /// Rolled every time the interval runs out
+    #[serde(default = "default_encounter_chance")]
+    pub chance: f32,
+    /// Seconds of walking between rolls
+    #[serde(default = "default_encounter_interval")]
+    pub interval: f32,
+    /// Enemy names from the enemy definitions, empty means any of them
+    #[serde(default)]
+    pub enemies: Vec<String>,
+    /// Off for areas where every fight has to be seen through
+    #[serde(default = "default_can_run")]
+    pub can_run: bool,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub enum NpcKind {
+    Healer,
+    // Name of a shop from the shop definitions
+    Merchant(String),
+}
+
+/// Door to another map, the player lands on tile (x, y) there
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct WarpTarget {
+    pub map: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// What a character in the grid stands for
+#[derive(Deserialize, Clone)]
+pub struct LegendEntry {
+    // Index into the ground tileset
+    pub tile: usize,
+    #[serde(default)]
+    pub solid: bool,
+    #[serde(default)]
+    pub encounter: bool,
+    // Index into the world objects tileset, drawn over the tile
+    #[serde(default)]
+    pub object: Option<usize>,
+    #[serde(default)]
+    pub npc: Option<NpcKind>,
+    #[serde(default)]
+    pub warp: Option<WarpTarget>,
+    // Where a new game starts
+    #[serde(default)]
+    pub spawn: bool,
+}
+
+#[derive(Deserialize)]
+struct MapHeader {
+    name: String,
+    #[serde(default)]
+    encounters: EncounterSettings,
+    legend: HashMap<char, LegendEntry>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub index: usize,
+    pub solid: bool,
+    pub encounter: bool,
+    pub object: Option<usize>,
+}
+
+/// A parsed map, placements are in tile coordinates
+pub struct MapData {
+    pub name: String,
+    pub encounters: EncounterSettings,
+    // Rows aren't padded, a short row just has fewer tiles
+    pub rows: Vec<Vec<Tile>>,
+    pub npcs: Vec<(usize, usize, NpcKind)>,
+    pub warps: Vec<(usize, usize, WarpTarget)>,
+    // Should be exactly one, the map checker complains otherwise
+    pub spawns: Vec<(usize, usize)>,
+    // Line in the file the first row of tiles is on, Tiled maps just count rows from 1
+    pub first_line: usize,
+}
+
+/// Grid characters that aren't in the legend, one message per cell
+#[derive(Debug)]
+pub struct LegendErrors(pub Vec<String>);
+
+impl fmt::Display for LegendErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join("\n"))
+    }
+}
+
+impl std::error::Error for LegendErrors {}
+
+impl Default for EncounterSettings {
+    fn default() -> Self {
+        EncounterSettings {
+            chance: default_encounter_chance(),
+            interval: default_encounter_interval(),
+            enemies: Vec::new(),
+            can_run: default_can_run(),
+        }
+    }
+}
+
+impl MapData {
+    pub fn spawn(&self) -> Option<(usize, usize)> {
+        self.spawns.first().copied()
+    }
+
+    /// Where a tile is for whoever edits the file, "line 20, column 4"
+    pub fn describe_tile(&self, x: usize, y: usize) -> String {
+        format!("line {}, column {}", self.first_line + y, x + 1)
+    }
+}
+
+/// Parses a map file, a RON header with the name, encounters and legend,
+/// then a `---` line and the tile grid using the legend characters
+pub fn parse_map(text: &str) -> anyhow::Result<MapData> {
+    let mut header_text = String::new();
+    let mut lines = text.lines().enumerate();
+    let mut first_line = 0;
+    for (line_index, line) in lines.by_ref() {
+        if line.trim() == GRID_SEPARATOR {
+            first_line = line_index + 2;
+            break;
+        }
+        header_text.push_str(line);
+        header_text.push('\n');
+    }
+    let header: MapHeader = ron::de::from_str(&header_text).context("Bad map header")?;
+
+    let mut map = MapData {
+        name: header.name,
+        encounters: header.encounters,
+        rows: Vec::new(),
+        npcs: Vec::new(),
+        warps: Vec::new(),
+        spawns: Vec::new(),
+        first_line: first_line,
+    };
+    let mut legend_errors = Vec::new();
+    for (line_index, line) in lines {
+        let mut row = Vec::new();
+        for (x, char) in line.trim_end_matches('\r').chars().enumerate() {
+            let entry = match header.legend.get(&char) {
+                Some(entry) => entry,
+                None => {
+                    // Keep going, all of them get reported at once
+                    legend_errors.push(format!(
+                        "Line {}, column {}: '{}' isn't in the legend",
+                        line_index + 1,
+                        x + 1,
+                        char
+                    ));
+                    continue;
+                }
+            };
+            let y = map.rows.len();
+            row.push(Tile {
+                index: entry.tile,
+                solid: entry.solid,
+                encounter: entry.encounter,
+                object: entry.object,
+            });
+            if let Some(npc) = &entry.npc {
+                map.npcs.push((x, y, npc.clone()));
+            }
+            if let Some(warp) = &entry.warp {
+                map.warps.push((x, y, warp.clone()));
+            }
+            if entry.spawn {
+                map.spawns.push((x, y));
+            }
+        }
+        map.rows.push(row);
+    }
+    if !legend_errors.is_empty() {
+        return Err(LegendErrors(legend_errors).into());
+    }
+
+    // Blank lines at the end of the file aren't part of the map
+    while map.rows.last().is_some_and(|row| row.is_empty()) {
+        map.rows.pop();
+    }
+    if map.rows.is_empty() {
+        bail!("The map has no tiles below the {} line", GRID_SEPARATOR);
+    }
+    Ok(map)
+}
+
+/// Our own format, or a map saved from Tiled when the file ends in .tmj
+pub fn parse_map_file(path: &Path, text: &str) -> anyhow::Result<MapData> {
+    let map = if path.extension().is_some_and(|extension| extension == "tmj") {
+        parse_tiled_map(text)
+    } else {
+        parse_map(text)
+    };
+    map.with_context(|| format!("Couldn't load {}", path.display()))
+}
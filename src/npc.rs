@@ -1,16 +1,22 @@
 use bevy::{prelude::*, render::camera::Camera2d};
 use bevy_kira_audio::{Audio};
 
-use crate::{combat::CombatStats, player::Player, ascii::{AsciiSheet, NineSliceIndices, spawn_nine_slice, spawn_ascii_sprite, spawn_ascii_text}, TILE_SIZE, CLEAR, GameState, audio::AudioState};
+use crate::{combat::CombatStats, player::Player, ascii::{AsciiSheet, NineSliceIndices, spawn_nine_slice, spawn_ascii_sprite, spawn_ascii_text}, TILE_SIZE, CLEAR, GameState, audio::AudioState, shop_menu::ShopMenuState, save::WorldFlags, save_menu::{SaveMenuMode, SaveMenuState}, tilemap::CurrentMap};
 
 pub struct NpcPlugin;
 
 #[derive(Component)]
 pub struct NpcText;
 
+/// Closing this text opens the save slots screen
+#[derive(Component)]
+pub struct SaveOffer;
+
 #[derive(Component)]
 pub enum Npc {
-    Healer
+    Healer,
+    // Name of the shop in game.shops.ron
+    Merchant(String),
 }
 
 impl Plugin for NpcPlugin {
@@ -26,14 +32,23 @@ impl Plugin for NpcPlugin {
 fn clear_speech(
     mut commands: Commands,
     mut player_query: Query<&mut Player>,
-    speech_query: Query<Entity, With<NpcText>>,
-    keyboard: Res<Input<KeyCode>>
+    speech_query: Query<(Entity, Option<&SaveOffer>), With<NpcText>>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut save_menu: ResMut<SaveMenuState>,
+    mut state: ResMut<State<GameState>>,
 ) {
     let mut player = player_query.single_mut();
     if keyboard.any_just_pressed([KeyCode::E, KeyCode::Space]) {
-        for ent in speech_query.iter() {
+        for (ent, save_offer) in speech_query.iter() {
             player.active = true;
             commands.entity(ent).despawn_recursive();
+            if save_offer.is_some() {
+                save_menu.mode = SaveMenuMode::Save;
+                state.push(GameState::SaveSlots).unwrap();
+                keyboard.clear_just_pressed(KeyCode::E);
+                keyboard.clear_just_pressed(KeyCode::Space);
+                return;
+            }
         }
     }
 }
@@ -77,11 +92,15 @@ fn npc_speech(
     mut player_query: Query<(&mut Player, &mut CombatStats, &Transform)>,
     camera_query: Query<&Transform, With<Camera2d>>,
     npc_query: Query<(&Npc, &Transform)>,
-    keyboard: Res<Input<KeyCode>>,
+    mut keyboard: ResMut<Input<KeyCode>>,
     ascii: Res<AsciiSheet>,
     indices: Res<NineSliceIndices>,
     audio: Res<Audio>, 
-    audio_state: Res<AudioState>
+    audio_state: Res<AudioState>,
+    mut shop_state: ResMut<ShopMenuState>,
+    mut state: ResMut<State<GameState>>,
+    mut flags: ResMut<WorldFlags>,
+    current_map: Res<CurrentMap>,
 ) {
     let (mut player, mut stats, transform) = player_query.single_mut();
     let camera_transform = camera_query.single();
@@ -90,24 +109,44 @@ fn npc_speech(
     }
 
     if keyboard.just_pressed(KeyCode::E) {
-        for (_npc, npc_transform) in npc_query.iter() {
+        for (npc, npc_transform) in npc_query.iter() {
             if Vec2::distance(
                 npc_transform.translation.truncate(),
                 transform.translation.truncate())
                 < TILE_SIZE * 1.5 {
-                    player.active = false;
-                    stats.health = stats.max_health;
+                    match npc {
+                        Npc::Healer => {
+                            player.active = false;
+                            player.respawn_map = current_map.0.clone();
+                            player.respawn_point = transform.translation;
+                            stats.health = stats.max_health;
 
-                    audio.play_in_channel(audio_state.heal_handle.clone(), &audio_state.sfx_channel);
+                            audio.play_in_channel(audio_state.heal_handle.clone(), &audio_state.sfx_channel);
 
-                    spawn_textbox(
-                        &mut commands,
-                        &ascii,
-                        &indices,
-                        Vec2::new(0.0, 1.0 - 1.5 * TILE_SIZE) + camera_transform.translation.truncate(),
-                        "You seem weak, let me heal you."
-                    );
+                            let speech = if flags.is_set("met_healer") {
+                                "Welcome back, rest a while."
+                            } else {
+                                "You seem weak, let me heal you."
+                            };
+                            flags.set("met_healer");
+                            let textbox = spawn_textbox(
+                                &mut commands,
+                                &ascii,
+                                &indices,
+                                Vec2::new(0.0, 1.0 - 1.5 * TILE_SIZE) + camera_transform.translation.truncate(),
+                                speech
+                            );
+                            // Resting is also where the game gets saved
+                            commands.entity(textbox).insert(SaveOffer);
+                        }
+                        Npc::Merchant(shop) => {
+                            shop_state.shop = shop.clone();
+                            state.push(GameState::Shop).unwrap();
+                            keyboard.clear_just_pressed(KeyCode::E);
+                            return;
+                        }
+                    }
                 }
         }
     }
-}
\ No newline at end of file
+}
@@ -1,12 +1,21 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, sprite::collide_aabb::collide, render::camera::Camera2d};
 use bevy_inspector_egui::Inspectable;
 
 use crate::{
     ascii::{AsciiSheet},
     combat::CombatStats,
+    equipment::{BaseStats, Equipment, StatBlock},
     fadeout::create_fadeout,
-    tilemap::{EncounterSpawner, TileCollider},
+    tilemap::{CurrentArea, CurrentMap, EncounterSpawner, MapBuild, PendingWarp, TileCollider, Warp},
     GameState, TILE_SIZE, graphics::{CharacterSheet, FrameAnimation, PlayerGraphics, FacingDirection},
+    rng::GameRng,
+    spells::Spellbook,
+    items::{Inventory, ItemStack},
+    progression::{LevelUp, Progression},
+    save::PendingLoad,
+    status::StatusEffects,
 };
 
 pub struct PlayerPlugin;
@@ -22,23 +31,43 @@ pub struct Player {
     speed: f32,
     pub active: bool,
     just_moved: bool,
+    pub level: usize,
+    // Progress towards the next level
     pub exp: usize,
+    pub gold: usize,
+    // Last healer visited, can be on another map
+    pub respawn_map: String,
+    pub respawn_point: Vec3,
 }
 
 impl Player {
+    /// Level ups grow the base stats, gear bonuses get added back on top afterwards.
+    /// Returns every level gained, a big reward can be worth several
     pub fn give_exp(
-        &mut self, exp: usize, stats: &mut CombatStats
-    ) -> bool {
+        &mut self,
+        exp: usize,
+        base: &mut BaseStats,
+        stats: &mut CombatStats,
+        progression: &Progression,
+    ) -> Vec<LevelUp> {
         self.exp += exp;
-        if self.exp >= 50 {
-            stats.health += 2;
-            stats.max_health += 2;
-            stats.attack += 2;
-            stats.defense += 2;
-            self.exp -= 50;
-            return true;
+        let mut level_ups = Vec::new();
+        while self.exp >= progression.exp_to_next(self.level) {
+            self.exp -= progression.exp_to_next(self.level);
+            self.level += 1;
+
+            let before = base.0;
+            let growth = progression.growth.at(self.level);
+            base.0 = before.with_bonus(&growth);
+            // The new health is there right away, mana waits for a rest
+            stats.health += growth.max_health;
+            level_ups.push(LevelUp {
+                level: self.level,
+                before: before,
+                after: base.0,
+            });
         }
-        false
+        level_ups
     }
 }
 
@@ -49,10 +78,12 @@ impl Plugin for PlayerPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Overworld)
                     .with_system(player_encounter_checking.after(player_movement))
+                    .with_system(player_warp_checking.after(player_movement))
                     .with_system(camera_follow.after(player_movement))
                     .with_system(player_movement),
             )
-            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(spawn_player));
+            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(spawn_player))
+            .add_system_set(SystemSet::on_exit(GameState::Overworld).with_system(despawn_player));
     }
 }
 
@@ -97,8 +128,11 @@ fn player_encounter_checking(
     encounter_query: Query<&Transform, (With<EncounterSpawner>, Without<Player>)>,
     ascii: Res<AsciiSheet>,
     time: Res<Time>,
+    area: Res<CurrentArea>,
+    mut rng: ResMut<GameRng>,
 ) {
     let (mut player, mut encounter_tracker, player_transform) = player_query.single_mut();
+    let encounters = &area.encounters;
     let player_translation = player_transform.translation;
 
     if player.just_moved
@@ -106,15 +140,43 @@ fn player_encounter_checking(
             .iter()
             .any(|&transform| wall_collision_check(player_translation, transform.translation))
     {
+        // Each area rolls at its own pace
+        encounter_tracker.timer.set_duration(Duration::from_secs_f32(encounters.interval));
         encounter_tracker.timer.tick(time.delta());
 
-        if encounter_tracker.timer.just_finished() {
+        if encounter_tracker.timer.just_finished() && rng.chance(encounters.chance) {
             player.active = false;
             create_fadeout(&mut commands, Some(GameState::Combat), &ascii);
         }
     }
 }
 
+fn player_warp_checking(
+    mut commands: Commands,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    warp_query: Query<(&Warp, &Transform), Without<Player>>,
+    ascii: Res<AsciiSheet>,
+    mut pending_warp: ResMut<PendingWarp>,
+) {
+    let (mut player, player_transform) = player_query.single_mut();
+    if !player.just_moved || !player.active {
+        return;
+    }
+
+    // Only once the player is mostly standing on the door
+    let warp = warp_query.iter().find(|(_, transform)| {
+        Vec2::distance(
+            transform.translation.truncate(),
+            player_transform.translation.truncate(),
+        ) < TILE_SIZE * 0.5
+    });
+    if let Some((warp, _)) = warp {
+        player.active = false;
+        pending_warp.0 = Some(warp.destination());
+        create_fadeout(&mut commands, Some(GameState::Warp), &ascii);
+    }
+}
+
 fn camera_follow(
     player_query: Query<&Transform, With<Player>>,
     mut camera_query: Query<&mut Transform, (Without<Player>, With<Camera2d>)>,
@@ -203,7 +265,83 @@ fn wall_collision_check(target_player_pos: Vec3, wall_translation: Vec3) -> bool
     collision.is_some()
 }
 
-fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
+fn despawn_player(mut commands: Commands, player_query: Query<Entity, With<Player>>) {
+    for player in player_query.iter() {
+        commands.entity(player).despawn_recursive();
+    }
+}
+
+fn spawn_player(
+    mut commands: Commands,
+    characters: Res<CharacterSheet>,
+    current_map: Res<CurrentMap>,
+    mut map_build: ResMut<MapBuild>,
+    mut pending_load: ResMut<PendingLoad>,
+) {
+    let base_stats = StatBlock {
+        max_health: 10,
+        max_mana: 5,
+        attack: 2,
+        defense: 1,
+        speed: 4,
+        accuracy: 95,
+        evasion: 5,
+        crit_rate: 10,
+    };
+    // Moved onto the map's spawn marker once the map is built
+    let spawn_point = Vec3::new(0.0, 0.0, 900.0);
+    let mut player = Player {
+        speed: 3.0,
+        active: true,
+        just_moved: false,
+        level: 1,
+        exp: 0,
+        gold: 20,
+        respawn_map: current_map.0.clone(),
+        respawn_point: spawn_point,
+    };
+    let mut translation = spawn_point;
+    let mut base_stats = BaseStats(base_stats);
+    let mut stats = base_stats.0.to_combat_stats(base_stats.0.max_health, base_stats.0.max_mana);
+    let mut equipment = Equipment::default();
+    let mut spellbook = Spellbook::default();
+    let mut inventory = Inventory {
+        stacks: vec![
+            ItemStack {
+                name: "Potion".to_string(),
+                count: 3,
+            },
+            ItemStack {
+                name: "Ether".to_string(),
+                count: 1,
+            },
+            ItemStack {
+                name: "Bronze Sword".to_string(),
+                count: 1,
+            },
+            ItemStack {
+                name: "Leather Vest".to_string(),
+                count: 1,
+            },
+        ],
+    };
+
+    // Continuing from the start menu, everything comes from the save instead
+    map_build.to_spawn = pending_load.0.is_none();
+    if let Some(save) = pending_load.0.take() {
+        translation = Vec3::from(save.position);
+        player.respawn_map = save.respawn_map;
+        player.respawn_point = Vec3::from(save.respawn_point);
+        player.level = save.level;
+        player.exp = save.exp;
+        player.gold = save.gold;
+        base_stats = BaseStats(save.base_stats);
+        stats = save.stats;
+        equipment = save.equipment;
+        spellbook.learned = save.learned_spells;
+        inventory.stacks = save.inventory;
+    }
+
     commands
         .spawn_bundle(SpriteSheetBundle {
             sprite: TextureAtlasSprite { 
@@ -211,7 +349,7 @@ fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
                 custom_size: Some(Vec2::splat(TILE_SIZE)),
                 ..default()
              },
-             transform: Transform::from_xyz(2.0 * TILE_SIZE, -2.0 * TILE_SIZE, 900.0),
+             transform: Transform::from_translation(translation),
              texture_atlas: characters.handle.clone(),
              ..default()
         })
@@ -224,21 +362,14 @@ fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
             facing: FacingDirection::Down
         })
         .insert(Name::new("Player"))
-        .insert(Player {
-            speed: 3.0,
-            active: true,
-            just_moved: false,
-            exp: 0,
-        })
+        .insert(player)
         .insert(EncounterTracker {
-            timer: Timer::from_seconds(1.0, true),
+            timer: Timer::from_seconds(0.5, true),
         })
-        .insert(CombatStats {
-            health: 10,
-            max_health: 10,
-            mana: 5,
-            max_mana: 5,
-            attack: 2,
-            defense: 1,
-        });
+        .insert(spellbook)
+        .insert(StatusEffects::default())
+        .insert(inventory)
+        .insert(base_stats)
+        .insert(equipment)
+        .insert(stats);
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::equipment::StatBlock;
+
+pub struct ProgressionPlugin;
+
+/// Exp needed to go from level to level + 1 is base * level ^ exponent
+#[derive(Deserialize)]
+pub struct ExpCurve {
+    pub base: f32,
+    pub exponent: f32,
+}
+
+/// What each level up adds to a stat, the first entry is for reaching level 2.
+/// The last entry keeps repeating once a table runs out.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct StatGrowth {
+    pub max_health: Vec<isize>,
+    pub max_mana: Vec<isize>,
+    pub attack: Vec<isize>,
+    pub defense: Vec<isize>,
+    pub speed: Vec<isize>,
+    pub accuracy: Vec<isize>,
+    pub evasion: Vec<isize>,
+    pub crit_rate: Vec<isize>,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "3f1a8c52-d94e-4b07-a6e3-5b2c0e7d9184"]
+pub struct Progression {
+    pub exp_curve: ExpCurve,
+    pub growth: StatGrowth,
+}
+
+pub struct ProgressionDefinition(pub Handle<Progression>);
+
+/// Base stats around one level gained, for the reward screen
+pub struct LevelUp {
+    pub level: usize,
+    pub before: StatBlock,
+    pub after: StatBlock,
+}
+
+#[derive(Default)]
+pub struct ProgressionLoader;
+
+fn growth_at(table: &[isize], index: usize) -> isize {
+    table.get(index).or_else(|| table.last()).copied().unwrap_or(0)
+}
+
+impl StatGrowth {
+    /// Stats gained when reaching the given level
+    pub fn at(&self, level: usize) -> StatBlock {
+        let index = level.saturating_sub(2);
+        StatBlock {
+            max_health: growth_at(&self.max_health, index),
+            max_mana: growth_at(&self.max_mana, index),
+            attack: growth_at(&self.attack, index),
+            defense: growth_at(&self.defense, index),
+            speed: growth_at(&self.speed, index),
+            accuracy: growth_at(&self.accuracy, index),
+            evasion: growth_at(&self.evasion, index),
+            crit_rate: growth_at(&self.crit_rate, index),
+        }
+    }
+}
+
+impl Progression {
+    pub fn exp_to_next(&self, level: usize) -> usize {
+        let curve = &self.exp_curve;
+        // Never 0, or a single reward would level up forever
+        ((curve.base * (level as f32).powf(curve.exponent)).round() as usize).max(1)
+    }
+}
+
+impl AssetLoader for ProgressionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let progression = ron::de::from_bytes::<Progression>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(progression));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["progression.ron"]
+    }
+}
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Progression>()
+            .init_asset_loader::<ProgressionLoader>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_progression);
+    }
+}
+
+fn load_progression(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(ProgressionDefinition(assets.load("data/game.progression.ron")));
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub struct RngPlugin;
+
+/// Every gameplay roll goes through here so runs can be replayed from a seed
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let rng = GameRng::new(seed_from_args().unwrap_or_else(rand::random));
+        // Quote this in bug reports, `--seed` replays it
+        info!("Gameplay seed: {}", rng.seed());
+        app.insert_resource(rng);
+    }
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed: seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        *self = GameRng::new(seed);
+    }
+
+    /// Draws a new seed and carries on from it, so a save can pick up the same rolls
+    pub fn fork_seed(&mut self) -> u64 {
+        let seed = self.rng.gen();
+        self.reseed(seed);
+        seed
+    }
+
+    /// Uniform roll in 0..1
+    pub fn roll(&mut self) -> f32 {
+        self.rng.gen()
+    }
+
+    pub fn chance(&mut self, chance: f32) -> bool {
+        self.roll() < chance
+    }
+
+    pub fn range(&mut self, range: std::ops::Range<usize>) -> usize {
+        self.rng.gen_range(range)
+    }
+}
+
+/// Reads `--seed <number>` or `--seed=<number>` from the command line
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--seed") {
+            Some("") => args.next(),
+            Some(value) => value.strip_prefix('=').map(|value| value.to_string()),
+            None => continue,
+        };
+        match value.as_deref().map(str::parse) {
+            Some(Ok(seed)) => return Some(seed),
+            _ => warn!("Ignoring bad --seed argument"),
+        }
+    }
+    None
+}
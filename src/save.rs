@@ -0,0 +1,232 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::CombatStats,
+    equipment::{BaseStats, Equipment, StatBlock},
+    items::{Inventory, ItemStack},
+    player::Player,
+    rng::GameRng,
+    spells::Spellbook,
+    tilemap::{CurrentArea, CurrentMap},
+    GameState,
+};
+
+// Bump when SaveData changes shape, older files get refused instead of misread
+pub const SAVE_VERSION: u32 = 4;
+pub const SLOT_COUNT: usize = 3;
+
+pub struct SavePlugin;
+
+/// Story progress that outlives the current map, like who the player talked to
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct WorldFlags {
+    flags: HashSet<String>,
+}
+
+/// Seconds spent in the game since it was started, not counting the title screen
+#[derive(Default)]
+pub struct Playtime(pub f64);
+
+/// Everything needed to put the overworld back the way it was
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    // Shown on the save slots screen
+    pub location: String,
+    pub playtime: f64,
+    pub saved_at: u64,
+
+    pub map: String,
+    pub position: [f32; 3],
+    pub respawn_map: String,
+    pub respawn_point: [f32; 3],
+    pub level: usize,
+    pub exp: usize,
+    pub gold: usize,
+    pub base_stats: StatBlock,
+    pub stats: CombatStats,
+    pub equipment: Equipment,
+    pub inventory: Vec<ItemStack>,
+    pub learned_spells: Vec<String>,
+    pub flags: WorldFlags,
+    pub rng_seed: u64,
+}
+
+/// A save picked on the save slots screen, the player gets spawned from it
+#[derive(Default)]
+pub struct PendingLoad(pub Option<SaveData>);
+
+impl WorldFlags {
+    pub fn set(&mut self, flag: &str) {
+        self.flags.insert(flag.to_string());
+    }
+
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+}
+
+impl SaveData {
+    /// Snapshot of the player and the world around them
+    pub fn capture(
+        player: (&Player, &Transform, &BaseStats, &CombatStats, &Equipment, &Inventory, &Spellbook),
+        current_map: &CurrentMap,
+        area: &CurrentArea,
+        flags: &WorldFlags,
+        playtime: &Playtime,
+        rng: &mut GameRng,
+    ) -> SaveData {
+        let (player, transform, base_stats, stats, equipment, inventory, spellbook) = player;
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        SaveData {
+            version: SAVE_VERSION,
+            location: area.name.clone(),
+            playtime: playtime.0,
+            saved_at: saved_at,
+            map: current_map.0.clone(),
+            position: transform.translation.to_array(),
+            respawn_map: player.respawn_map.clone(),
+            respawn_point: player.respawn_point.to_array(),
+            level: player.level,
+            exp: player.exp,
+            gold: player.gold,
+            base_stats: base_stats.0,
+            stats: stats.clone(),
+            equipment: equipment.clone(),
+            inventory: inventory.stacks.clone(),
+            learned_spells: spellbook.learned.clone(),
+            flags: flags.clone(),
+            // Loading carries on from here with the same rolls
+            rng_seed: rng.fork_seed(),
+        }
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldFlags>()
+            .init_resource::<PendingLoad>()
+            .init_resource::<Playtime>()
+            .add_system(count_playtime);
+    }
+}
+
+pub fn save_path(slot: usize) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("dreadblaze").join(format!("save{}.ron", slot + 1)))
+}
+
+pub fn any_save_exists() -> bool {
+    (0..SLOT_COUNT).filter_map(save_path).any(|path| path.exists())
+}
+
+pub fn write_save(slot: usize, save: &SaveData) -> anyhow::Result<()> {
+    let path = save_path(slot).context("No user data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())?;
+    fs::write(&path, text).with_context(|| format!("Couldn't write {}", path.display()))?;
+    Ok(())
+}
+
+/// None for an empty slot
+pub fn read_save(slot: usize) -> anyhow::Result<Option<SaveData>> {
+    let path = save_path(slot).context("No user data directory")?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = fs::read(&path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    let save: SaveData = ron::de::from_bytes(&bytes)?;
+    if save.version != SAVE_VERSION {
+        bail!("Save version {} isn't supported, expected {}", save.version, SAVE_VERSION);
+    }
+    Ok(Some(save))
+}
+
+/// The most recently written slot, slots that can't be read are passed over
+pub fn read_latest_save() -> Option<SaveData> {
+    (0..SLOT_COUNT)
+        .filter_map(|slot| match read_save(slot) {
+            Ok(save) => save,
+            Err(err) => {
+                warn!("Save slot {} can't be read: {:#}", slot + 1, err);
+                None
+            }
+        })
+        .max_by_key(|save| save.saved_at)
+}
+
+/// Puts the world back the way the save has it, the player is spawned from
+/// PendingLoad once the overworld is entered again
+pub fn begin_load(
+    commands: &mut Commands,
+    save: SaveData,
+    pending_load: &mut PendingLoad,
+    rng: &mut GameRng,
+) {
+    commands.insert_resource(CurrentMap(save.map.clone()));
+    commands.insert_resource(save.flags.clone());
+    commands.insert_resource(Playtime(save.playtime));
+    rng.reseed(save.rng_seed);
+    pending_load.0 = Some(save);
+}
+
+pub fn delete_save(slot: usize) -> anyhow::Result<()> {
+    let path = save_path(slot).context("No user data directory")?;
+    fs::remove_file(&path).with_context(|| format!("Couldn't delete {}", path.display()))?;
+    Ok(())
+}
+
+/// Hours and minutes, "1:02"
+pub fn format_playtime(seconds: f64) -> String {
+    let minutes = seconds as u64 / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// "2022-06-01 18:30" in UTC
+pub fn format_timestamp(seconds: u64) -> String {
+    // Days to a civil date, from Howard Hinnant's date algorithms
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let minutes = seconds / 60;
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60 % 24,
+        minutes % 60
+    )
+}
+
+fn count_playtime(mut playtime: ResMut<Playtime>, state: Res<State<GameState>>, time: Res<Time>) {
+    // The title screen stays right under the save slots screen when that's opened from it,
+    // deeper down it's only the bottom of the stack a game was pushed onto
+    let on_title = match state.current() {
+        GameState::StartMenu => true,
+        GameState::SaveSlots => state.inactives().last() == Some(&GameState::StartMenu),
+        _ => false,
+    };
+    if !on_title {
+        playtime.0 += time.delta_seconds_f64();
+    }
+}
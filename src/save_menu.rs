@@ -0,0 +1,364 @@
+use bevy::{prelude::*, render::camera::Camera2d};
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_colored_ascii_text, spawn_list_menu, AsciiSheet, NineSliceIndices},
+    combat::CombatStats,
+    equipment::{BaseStats, Equipment},
+    fadeout::{create_transition_fadeout, FadeTransition},
+    items::Inventory,
+    player::Player,
+    rng::GameRng,
+    save::{
+        begin_load, delete_save, format_playtime, format_timestamp, read_save, write_save,
+        PendingLoad, Playtime, SaveData, WorldFlags, SLOT_COUNT,
+    },
+    spells::Spellbook,
+    tilemap::{CurrentArea, CurrentMap},
+    GameState, TILE_SIZE,
+};
+
+pub struct SaveMenuPlugin;
+
+#[derive(Component)]
+pub struct SaveSlotsScreen;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SaveMenuMode {
+    Save,
+    Load,
+}
+
+/// Waiting on a yes or no before touching a slot that's in use
+#[derive(Clone, Copy)]
+enum SlotAction {
+    Overwrite,
+    Delete,
+}
+
+enum SlotInfo {
+    Empty,
+    // Corrupt or from an older version
+    Unreadable,
+    Saved {
+        level: usize,
+        location: String,
+        playtime: f64,
+        saved_at: u64,
+    },
+}
+
+pub struct SaveMenuState {
+    // Set before entering the state, saving only works with a player around
+    pub mode: SaveMenuMode,
+    selected: usize,
+    confirm: Option<SlotAction>,
+    // A load is fading in, nothing else should happen
+    loading: bool,
+    message: String,
+    slots: Vec<SlotInfo>,
+}
+
+impl Default for SaveMenuState {
+    fn default() -> Self {
+        SaveMenuState {
+            mode: SaveMenuMode::Load,
+            selected: 0,
+            confirm: None,
+            loading: false,
+            message: String::new(),
+            slots: Vec::new(),
+        }
+    }
+}
+
+impl Plugin for SaveMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveMenuState>()
+            .add_system_set(SystemSet::on_enter(GameState::SaveSlots).with_system(reset_save_menu))
+            .add_system_set(
+                SystemSet::on_update(GameState::SaveSlots)
+                    .with_system(save_menu_input)
+                    .with_system(refresh_save_slots_screen.after(save_menu_input)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::SaveSlots).with_system(despawn_save_slots_screen),
+            );
+    }
+}
+
+fn read_slots() -> Vec<SlotInfo> {
+    (0..SLOT_COUNT)
+        .map(|slot| match read_save(slot) {
+            Ok(Some(save)) => SlotInfo::Saved {
+                level: save.level,
+                location: save.location,
+                playtime: save.playtime,
+                saved_at: save.saved_at,
+            },
+            Ok(None) => SlotInfo::Empty,
+            Err(err) => {
+                warn!("Save slot {} can't be read: {:#}", slot + 1, err);
+                SlotInfo::Unreadable
+            }
+        })
+        .collect()
+}
+
+fn reset_save_menu(mut menu_state: ResMut<SaveMenuState>) {
+    menu_state.selected = 0;
+    menu_state.confirm = None;
+    menu_state.loading = false;
+    menu_state.message.clear();
+    menu_state.slots = read_slots();
+}
+
+fn save_menu_input(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut menu_state: ResMut<SaveMenuState>,
+    player_query: Query<(
+        &Player,
+        &Transform,
+        &BaseStats,
+        &CombatStats,
+        &Equipment,
+        &Inventory,
+        &Spellbook,
+    )>,
+    current_map: Res<CurrentMap>,
+    area: Res<CurrentArea>,
+    flags: Res<WorldFlags>,
+    playtime: Res<Playtime>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut rng: ResMut<GameRng>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if menu_state.loading {
+        return;
+    }
+    let slot = menu_state.selected;
+
+    if let Some(action) = menu_state.confirm {
+        if keyboard.any_just_pressed([KeyCode::Y, KeyCode::Return]) {
+            menu_state.confirm = None;
+            match action {
+                SlotAction::Overwrite => {
+                    if let Ok(player) = player_query.get_single() {
+                        let save = SaveData::capture(player, &current_map, &area, &flags, &playtime, &mut rng);
+                        save_to_slot(&mut menu_state, slot, &save);
+                    }
+                }
+                SlotAction::Delete => {
+                    menu_state.message = match delete_save(slot) {
+                        Ok(()) => format!("Deleted slot {}", slot + 1),
+                        Err(err) => {
+                            error!("Deleting failed: {:#}", err);
+                            "Delete failed".to_string()
+                        }
+                    };
+                    menu_state.slots = read_slots();
+                }
+            }
+        } else if keyboard.any_just_pressed([KeyCode::N, KeyCode::Escape]) {
+            menu_state.confirm = None;
+            menu_state.message.clear();
+        }
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        state.pop().unwrap();
+        keyboard.clear_just_pressed(KeyCode::Escape);
+        return;
+    }
+
+    let mut new_selection = slot as isize;
+    if keyboard.just_pressed(KeyCode::W) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        new_selection += 1;
+    }
+    let new_selection = ((new_selection + SLOT_COUNT as isize) % SLOT_COUNT as isize) as usize;
+    if new_selection != slot {
+        menu_state.selected = new_selection;
+        menu_state.message.clear();
+        return;
+    }
+
+    // Switching to saving needs someone to save, so not from the title screen
+    let player = player_query.get_single().ok();
+    if keyboard.any_just_pressed([KeyCode::A, KeyCode::D]) && player.is_some() {
+        menu_state.mode = match menu_state.mode {
+            SaveMenuMode::Save => SaveMenuMode::Load,
+            SaveMenuMode::Load => SaveMenuMode::Save,
+        };
+        menu_state.message.clear();
+    }
+
+    let empty = matches!(menu_state.slots[slot], SlotInfo::Empty);
+    if keyboard.just_pressed(KeyCode::X) && !empty {
+        menu_state.confirm = Some(SlotAction::Delete);
+        return;
+    }
+
+    if !keyboard.just_pressed(KeyCode::Return) {
+        return;
+    }
+    match menu_state.mode {
+        SaveMenuMode::Save => {
+            if let Some(player) = player {
+                if empty {
+                    let save = SaveData::capture(player, &current_map, &area, &flags, &playtime, &mut rng);
+                    save_to_slot(&mut menu_state, slot, &save);
+                } else {
+                    menu_state.confirm = Some(SlotAction::Overwrite);
+                }
+            }
+        }
+        SaveMenuMode::Load => match read_save(slot) {
+            Ok(Some(save)) => {
+                begin_load(&mut commands, save, &mut pending_load, &mut rng);
+                menu_state.loading = true;
+                // Unwinds everything, the overworld gets built again from the save
+                create_transition_fadeout(
+                    &mut commands,
+                    FadeTransition::Replace(GameState::Overworld),
+                    &ascii,
+                );
+            }
+            Ok(None) => menu_state.message = "That slot is empty".to_string(),
+            Err(err) => {
+                error!("Loading failed: {:#}", err);
+                menu_state.message = "Can't load that save".to_string();
+            }
+        },
+    }
+}
+
+fn save_to_slot(menu_state: &mut SaveMenuState, slot: usize, save: &SaveData) {
+    menu_state.message = match write_save(slot, save) {
+        Ok(()) => format!("Saved to slot {}", slot + 1),
+        Err(err) => {
+            error!("Saving failed: {:#}", err);
+            "Save failed".to_string()
+        }
+    };
+    menu_state.slots = read_slots();
+}
+
+fn slot_line(slot: usize, info: &SlotInfo) -> String {
+    match info {
+        SlotInfo::Empty => format!("{} - Empty -", slot + 1),
+        SlotInfo::Unreadable => format!("{} Unreadable save", slot + 1),
+        SlotInfo::Saved {
+            level,
+            location,
+            playtime,
+            saved_at,
+        } => {
+            let location: String = location.chars().take(8).collect();
+            format!(
+                "{} Lv{:<3}{:<9}{:>6}  {}",
+                slot + 1,
+                level,
+                location,
+                format_playtime(*playtime),
+                format_timestamp(*saved_at)
+            )
+        }
+    }
+}
+
+fn refresh_save_slots_screen(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    menu_state: Res<SaveMenuState>,
+    player_query: Query<&Player>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    screen_query: Query<Entity, With<SaveSlotsScreen>>,
+) {
+    if !menu_state.is_changed() {
+        return;
+    }
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let center = camera_query.single().translation.truncate().extend(100.0);
+    let mut entities = Vec::new();
+
+    // The open tab is bright, saving is missing on the title screen
+    let mut tabs = vec![("Load", SaveMenuMode::Load, 0.15)];
+    if !player_query.is_empty() {
+        tabs.push(("Save", SaveMenuMode::Save, -0.4));
+    }
+    for (label, mode, x) in tabs {
+        let color = if menu_state.mode == mode {
+            Color::rgb(0.9, 0.9, 0.9)
+        } else {
+            Color::rgb(0.4, 0.4, 0.4)
+        };
+        entities.push(spawn_colored_ascii_text(
+            &mut commands,
+            &ascii,
+            label,
+            center + Vec3::new(x, 0.6, 0.0),
+            color,
+        ));
+    }
+
+    let lines: Vec<(String, Color)> = menu_state
+        .slots
+        .iter()
+        .enumerate()
+        .map(|(slot, info)| (slot_line(slot, info), Color::rgb(0.8, 0.8, 0.8)))
+        .collect();
+    entities.push(spawn_list_menu(
+        &mut commands,
+        &ascii,
+        &indices,
+        &lines,
+        menu_state.selected,
+        center + Vec3::new(0.0, 0.2, 0.0),
+    ));
+
+    let message = match menu_state.confirm {
+        Some(SlotAction::Overwrite) => format!("Overwrite slot {}? Y/N", menu_state.selected + 1),
+        Some(SlotAction::Delete) => format!("Delete slot {}? Y/N", menu_state.selected + 1),
+        None => menu_state.message.clone(),
+    };
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &message,
+        center + Vec3::new(-((message.len() / 2) as f32 * TILE_SIZE), -0.3, 0.0),
+    ));
+
+    let hint_text = match menu_state.mode {
+        SaveMenuMode::Save => "Enter: save  X: delete  A/D: load  Esc: back",
+        SaveMenuMode::Load if player_query.is_empty() => "Enter: load  X: delete  Esc: back",
+        SaveMenuMode::Load => "Enter: load  X: delete  A/D: save  Esc: back",
+    };
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        hint_text,
+        center + Vec3::new(-((hint_text.len() / 2) as f32 * TILE_SIZE), -0.85, 0.0),
+    ));
+
+    for entity in entities {
+        commands.entity(entity).insert(SaveSlotsScreen);
+    }
+}
+
+fn despawn_save_slots_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<SaveSlotsScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
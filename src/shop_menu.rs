@@ -0,0 +1,271 @@
+use bevy::{prelude::*, render::camera::Camera2d};
+
+use crate::{
+    ascii::{spawn_ascii_text, spawn_colored_ascii_text, spawn_list_menu, AsciiSheet, NineSliceIndices},
+    items::{Inventory, Item, ItemDefinitions, ItemList},
+    player::Player,
+    shops::{ShopDefinitions, ShopList},
+    GameState, TILE_SIZE,
+};
+
+pub struct ShopMenuPlugin;
+
+#[derive(Component)]
+pub struct ShopScreen;
+
+#[derive(Default)]
+pub struct ShopMenuState {
+    // Name of the shop the merchant runs, set before entering the state
+    pub shop: String,
+    selling: bool,
+    selected: usize,
+    message: String,
+}
+
+impl Plugin for ShopMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShopMenuState>()
+            .add_system_set(SystemSet::on_enter(GameState::Shop).with_system(reset_shop_menu))
+            .add_system_set(
+                SystemSet::on_update(GameState::Shop)
+                    .with_system(shop_menu_input)
+                    .with_system(refresh_shop_screen.after(shop_menu_input)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Shop).with_system(despawn_shop_screen));
+    }
+}
+
+/// Rows of the current tab, the shop's stock or whatever the player carries.
+/// Names missing from the item list are left out, input and drawing both go by this
+fn shop_rows<'a>(
+    menu_state: &ShopMenuState,
+    shops: &ShopList,
+    items: &'a ItemList,
+    inventory: &Inventory,
+) -> Vec<&'a Item> {
+    let names: Vec<&String> = if menu_state.selling {
+        inventory.stacks.iter().map(|stack| &stack.name).collect()
+    } else {
+        shops
+            .get(&menu_state.shop)
+            .map_or(Vec::new(), |shop| shop.stock.iter().collect())
+    };
+    names.into_iter().filter_map(|name| items.get(name)).collect()
+}
+
+fn reset_shop_menu(mut menu_state: ResMut<ShopMenuState>) {
+    menu_state.selling = false;
+    menu_state.selected = 0;
+    menu_state.message.clear();
+}
+
+fn shop_menu_input(
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut player_query: Query<(&mut Player, &mut Inventory)>,
+    mut menu_state: ResMut<ShopMenuState>,
+    shop_definitions: Res<ShopDefinitions>,
+    shop_lists: Res<Assets<ShopList>>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keyboard.any_just_pressed([KeyCode::E, KeyCode::Escape]) {
+        state.pop().unwrap();
+        keyboard.clear_just_pressed(KeyCode::E);
+        return;
+    }
+    let (shops, items) = match (shop_lists.get(&shop_definitions.0), item_lists.get(&item_definitions.0)) {
+        (Some(shops), Some(items)) => (shops, items),
+        _ => return,
+    };
+
+    if keyboard.any_just_pressed([KeyCode::A, KeyCode::D]) {
+        menu_state.selling = !menu_state.selling;
+        menu_state.selected = 0;
+        menu_state.message.clear();
+    }
+
+    let (mut player, mut inventory) = player_query.single_mut();
+    let rows = shop_rows(&menu_state, shops, items, &inventory);
+    let row_count = rows.len() as isize;
+    if row_count == 0 {
+        return;
+    }
+
+    let mut new_selection = menu_state.selected as isize;
+    if keyboard.just_pressed(KeyCode::W) {
+        new_selection -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::S) {
+        new_selection += 1;
+    }
+    let new_selection = ((new_selection + row_count) % row_count) as usize;
+    if new_selection != menu_state.selected {
+        menu_state.selected = new_selection;
+    }
+
+    if !keyboard.just_pressed(KeyCode::Return) {
+        return;
+    }
+    let item = rows[menu_state.selected];
+
+    if menu_state.selling {
+        if item.sell_price() == 0 {
+            menu_state.message = format!("Can't sell {}", item.name);
+            return;
+        }
+        inventory.remove_one(&item.name);
+        player.gold += item.sell_price();
+        menu_state.message = format!("Sold {} for {}g", item.name, item.sell_price());
+        // The last of a stack is gone, keep the cursor in the list
+        let row_count = shop_rows(&menu_state, shops, items, &inventory).len();
+        menu_state.selected = menu_state.selected.min(row_count.saturating_sub(1));
+    } else if player.gold < item.price {
+        menu_state.message = "Not enough gold".to_string();
+    } else if inventory.add(item, 1) == 0 {
+        menu_state.message = format!("Can't carry more {}", item.name);
+    } else {
+        player.gold -= item.price;
+        menu_state.message = format!("Bought {}", item.name);
+    }
+}
+
+fn shop_row_text(item: &Item, menu_state: &ShopMenuState, inventory: &Inventory, name_width: usize) -> String {
+    if menu_state.selling {
+        let name = format!("{} x{}", item.name, inventory.count(&item.name));
+        format!("{:<width$} {:>3}g", name, item.sell_price(), width = name_width + 3)
+    } else {
+        format!("{:<width$} {:>3}g", item.name, item.price, width = name_width)
+    }
+}
+
+fn refresh_shop_screen(
+    mut commands: Commands,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+    menu_state: Res<ShopMenuState>,
+    player_query: Query<(ChangeTrackers<Player>, ChangeTrackers<Inventory>, &Player, &Inventory)>,
+    shop_definitions: Res<ShopDefinitions>,
+    shop_lists: Res<Assets<ShopList>>,
+    item_definitions: Res<ItemDefinitions>,
+    item_lists: Res<Assets<ItemList>>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    screen_query: Query<Entity, With<ShopScreen>>,
+) {
+    let (player_tracker, inventory_tracker, player, inventory) = player_query.single();
+    if !menu_state.is_changed() && !player_tracker.is_changed() && !inventory_tracker.is_changed() {
+        return;
+    }
+    let (shops, items) = match (shop_lists.get(&shop_definitions.0), item_lists.get(&item_definitions.0)) {
+        (Some(shops), Some(items)) => (shops, items),
+        _ => return,
+    };
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let center = camera_query.single().translation.truncate().extend(100.0);
+    let mut entities = Vec::new();
+
+    let title = shops.get(&menu_state.shop).map_or("Shop", |shop| shop.title.as_str());
+    let gold_text = format!("{}  Gold: {}", title, player.gold);
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &gold_text,
+        center + Vec3::new(-((gold_text.len() / 2) as f32 * TILE_SIZE), 0.8, 0.0),
+    ));
+
+    // The open tab is bright, the other one dimmed
+    for (label, selling, x) in [("Buy", false, -0.4), ("Sell", true, 0.15)] {
+        let color = if menu_state.selling == selling {
+            Color::rgb(0.9, 0.9, 0.9)
+        } else {
+            Color::rgb(0.4, 0.4, 0.4)
+        };
+        entities.push(spawn_colored_ascii_text(
+            &mut commands,
+            &ascii,
+            label,
+            center + Vec3::new(x, 0.6, 0.0),
+            color,
+        ));
+    }
+
+    let rows = shop_rows(&menu_state, shops, items, inventory);
+    if rows.is_empty() {
+        let empty_text = if menu_state.selling { "Nothing to sell" } else { "Sold out" };
+        entities.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            empty_text,
+            center + Vec3::new(-((empty_text.len() / 2) as f32 * TILE_SIZE), 0.1, 0.0),
+        ));
+    } else {
+        let name_width = rows.iter().map(|item| item.name.len()).max().unwrap_or(0);
+        let lines: Vec<(String, Color)> = rows
+            .iter()
+            .map(|item| {
+                // Greyed out when the trade can't happen
+                let possible = if menu_state.selling {
+                    item.sell_price() > 0
+                } else {
+                    player.gold >= item.price
+                };
+                let color = if possible {
+                    Color::rgb(0.8, 0.8, 0.8)
+                } else {
+                    Color::rgb(0.4, 0.4, 0.4)
+                };
+                (shop_row_text(item, &menu_state, inventory, name_width), color)
+            })
+            .collect();
+        let selected = menu_state.selected.min(lines.len() - 1);
+        entities.push(spawn_list_menu(
+            &mut commands,
+            &ascii,
+            &indices,
+            &lines,
+            selected,
+            center + Vec3::new(0.0, 0.1, 0.0),
+        ));
+
+        let item = rows[selected];
+        let description = format!("{} (have {})", item.description, inventory.count(&item.name));
+        entities.push(spawn_ascii_text(
+            &mut commands,
+            &ascii,
+            &description,
+            center + Vec3::new(-((description.len() / 2) as f32 * TILE_SIZE), -0.5, 0.0),
+        ));
+    }
+
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        &menu_state.message,
+        center + Vec3::new(-((menu_state.message.len() / 2) as f32 * TILE_SIZE), -0.65, 0.0),
+    ));
+
+    let hint_text = if menu_state.selling {
+        "Enter: sell  A/D: buy  E: leave"
+    } else {
+        "Enter: buy  A/D: sell  E: leave"
+    };
+    entities.push(spawn_ascii_text(
+        &mut commands,
+        &ascii,
+        hint_text,
+        center + Vec3::new(-((hint_text.len() / 2) as f32 * TILE_SIZE), -0.85, 0.0),
+    ));
+
+    for entity in entities {
+        commands.entity(entity).insert(ShopScreen);
+    }
+}
+
+fn despawn_shop_screen(mut commands: Commands, screen_query: Query<Entity, With<ShopScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
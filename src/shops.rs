@@ -0,0 +1,65 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+pub struct ShopsPlugin;
+
+#[derive(Deserialize)]
+pub struct Shop {
+    // What merchants on the map refer to
+    pub name: String,
+    pub title: String,
+    // Item names from game.items.ron, there's always more in the back
+    pub stock: Vec<String>,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "b7e2945d-1c8a-4a6f-9d30-84f5e61c2a97"]
+pub struct ShopList {
+    pub shops: Vec<Shop>,
+}
+
+pub struct ShopDefinitions(pub Handle<ShopList>);
+
+#[derive(Default)]
+pub struct ShopListLoader;
+
+impl ShopList {
+    pub fn get(&self, name: &str) -> Option<&Shop> {
+        self.shops.iter().find(|shop| shop.name == name)
+    }
+}
+
+impl AssetLoader for ShopListLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let shop_list = ron::de::from_bytes::<ShopList>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(shop_list));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["shops.ron"]
+    }
+}
+
+impl Plugin for ShopsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ShopList>()
+            .init_asset_loader::<ShopListLoader>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_shops);
+    }
+}
+
+fn load_shops(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(ShopDefinitions(assets.load("data/game.shops.ron")));
+}
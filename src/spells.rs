@@ -0,0 +1,99 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{combat::AttackType, status::StatusKind};
+
+pub struct SpellsPlugin;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SpellTarget {
+    SingleEnemy,
+    AllEnemies,
+    Myself,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Spell {
+    pub name: String,
+    pub mana_cost: isize,
+    pub power: isize,
+    pub element: AttackType,
+    pub target: SpellTarget,
+    pub vfx_index: usize,
+    #[serde(default)]
+    pub status: Option<StatusKind>,
+    #[serde(default)]
+    pub starting: bool,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5f0e6b3c-2d1a-4c8e-9b47-3a6d2f81c0e5"]
+pub struct SpellList {
+    pub spells: Vec<Spell>,
+}
+
+pub struct SpellDefinitions(pub Handle<SpellList>);
+
+/// Spells the player learned on top of the starting ones
+#[derive(Component, Default)]
+pub struct Spellbook {
+    pub learned: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct SpellListLoader;
+
+impl Spellbook {
+    pub fn knows(&self, spell: &Spell) -> bool {
+        spell.starting || self.learned.contains(&spell.name)
+    }
+
+    /// Learns the first spell in the list that isn't known yet
+    pub fn learn_next<'a>(&mut self, spells: &'a SpellList) -> Option<&'a Spell> {
+        let spell = spells.spells.iter().find(|spell| !self.knows(spell))?;
+        self.learned.push(spell.name.clone());
+        Some(spell)
+    }
+}
+
+impl SpellList {
+    pub fn known<'a>(&'a self, spellbook: &'a Spellbook) -> impl Iterator<Item = &'a Spell> {
+        self.spells.iter().filter(|spell| spellbook.knows(spell))
+    }
+}
+
+impl AssetLoader for SpellListLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let spell_list = ron::de::from_bytes::<SpellList>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(spell_list));
+            Ok(())
+        })
+    }
+
+    // Bevy only matches what follows a dot in the file name, hence data/game.spells.ron
+    fn extensions(&self) -> &[&str] {
+        &["spells.ron"]
+    }
+}
+
+impl Plugin for SpellsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SpellList>()
+            .init_asset_loader::<SpellListLoader>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_spells);
+    }
+}
+
+fn load_spells(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(SpellDefinitions(assets.load("data/game.spells.ron")));
+}
@@ -1,12 +1,30 @@
 use bevy::{prelude::*, ui::FocusPolicy};
 
-use crate::{ascii::AsciiSheet, fadeout::create_fadeout, GameState};
+use crate::{
+    ascii::AsciiSheet,
+    fadeout::create_fadeout,
+    rng::GameRng,
+    save::{any_save_exists, begin_load, read_latest_save, PendingLoad, Playtime, WorldFlags},
+    save_menu::{SaveMenuMode, SaveMenuState},
+    tilemap::CurrentMap,
+    GameState,
+};
 
 pub struct MainMenuPlugin;
 
 #[derive(Component)]
 pub struct ButtonActive(bool);
 
+#[derive(Component, Clone, Copy)]
+pub enum StartMenuButton {
+    StartGame,
+    Continue,
+    LoadGame,
+}
+
+#[derive(Component)]
+pub struct StartMenu;
+
 struct UiAssets {
     font: Handle<Font>,
     button: Handle<Image>,
@@ -16,36 +34,72 @@ struct UiAssets {
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_menu)
+            .add_system_set(SystemSet::on_enter(GameState::StartMenu).with_system(spawn_menu))
             .add_system_set(SystemSet::on_pause(GameState::StartMenu).with_system(despawn_menu))
+            // Coming back from the save slots screen
+            .add_system_set(SystemSet::on_resume(GameState::StartMenu).with_system(spawn_menu))
+            .add_system_set(SystemSet::on_exit(GameState::StartMenu).with_system(despawn_menu))
             .add_system(handle_start_button);
     }
 }
 
-fn despawn_menu(mut commands: Commands, button_query: Query<Entity, With<Button>>) {
-    for ent in button_query.iter() {
+fn despawn_menu(mut commands: Commands, menu_query: Query<Entity, With<StartMenu>>) {
+    for ent in menu_query.iter() {
         commands.entity(ent).despawn_recursive();
     }
 }
 
 fn handle_start_button(
     mut commands: Commands,
-    mut interaction_query: Query<
-        (&Children, &mut ButtonActive, &Interaction),
+    interaction_query: Query<
+        (&Children, &StartMenuButton, &Interaction),
         Changed<Interaction>,
     >,
+    mut active_query: Query<&mut ButtonActive>,
     mut image_query: Query<&mut UiImage>,
     ui_assets: Res<UiAssets>,
     ascii: Res<AsciiSheet>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut rng: ResMut<GameRng>,
+    mut save_menu: ResMut<SaveMenuState>,
+    mut state: ResMut<State<GameState>>,
 ) {
-    for (children, mut active, interaction) in interaction_query.iter_mut() {
+    for (children, button, interaction) in interaction_query.iter() {
         let child = children.iter().next().unwrap();
         let mut image = image_query.get_mut(*child).unwrap();
 
         match interaction {
             Interaction::Clicked => {
-                if active.0 {
-                    image.0 = ui_assets.button_pressed.clone();
-                    create_fadeout(&mut commands, Some(GameState::Overworld), &ascii);
+                // One click is enough, the other buttons stop working too
+                if !active_query.iter().all(|active| active.0) {
+                    continue;
+                }
+                match button {
+                    StartMenuButton::StartGame => {
+                        commands.insert_resource(CurrentMap::default());
+                        commands.insert_resource(WorldFlags::default());
+                        commands.insert_resource(Playtime::default());
+                        pending_load.0 = None;
+                        create_fadeout(&mut commands, Some(GameState::Overworld), &ascii);
+                    }
+                    // Picks up from whichever slot was written last
+                    StartMenuButton::Continue => match read_latest_save() {
+                        Some(save) => {
+                            begin_load(&mut commands, save, &mut pending_load, &mut rng);
+                            create_fadeout(&mut commands, Some(GameState::Overworld), &ascii);
+                        }
+                        None => {
+                            error!("No save could be read to continue from");
+                            continue;
+                        }
+                    },
+                    StartMenuButton::LoadGame => {
+                        save_menu.mode = SaveMenuMode::Load;
+                        state.push(GameState::SaveSlots).unwrap();
+                    }
+                }
+                image.0 = ui_assets.button_pressed.clone();
+                for mut active in active_query.iter_mut() {
                     active.0 = false;
                 }
             }
@@ -64,20 +118,57 @@ fn setup_menu(mut commands: Commands, assets: Res<AssetServer>) {
     };
 
     commands.spawn_bundle(UiCameraBundle::default());
+    commands.insert_resource(ui_assets);
+}
+
+fn spawn_menu(mut commands: Commands, ui_assets: Res<UiAssets>) {
+    let mut buttons = vec![("Start Game", StartMenuButton::StartGame)];
+    if any_save_exists() {
+        buttons.insert(0, ("Continue", StartMenuButton::Continue));
+        buttons.push(("Load Game", StartMenuButton::LoadGame));
+    }
+
     commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                // Top to bottom, UI y goes up
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(StartMenu)
+        .with_children(|parent| {
+            for (label, button) in buttons {
+                spawn_button(parent, &ui_assets, label, button);
+            }
+        });
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    ui_assets: &UiAssets,
+    label: &str,
+    button: StartMenuButton,
+) {
+    parent
         .spawn_bundle(ButtonBundle {
             style: Style {
-                align_self: AlignSelf::Center,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 size: Size::new(Val::Percent(20.0), Val::Percent(10.0)),
-                margin: Rect::all(Val::Auto),
+                margin: Rect::all(Val::Percent(1.0)),
                 ..Default::default()
             },
             color: Color::NONE.into(),
             ..Default::default()
         })
         .insert(ButtonActive(true))
+        .insert(button)
         .with_children(|parent| {
             parent
                 .spawn_bundle(ImageBundle {
@@ -94,7 +185,7 @@ fn setup_menu(mut commands: Commands, assets: Res<AssetServer>) {
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle {
                         text: Text::with_section(
-                            "Start Game",
+                            label,
                             TextStyle {
                                 font: ui_assets.font.clone(),
                                 font_size: 40.0,
@@ -107,5 +198,4 @@ fn setup_menu(mut commands: Commands, assets: Res<AssetServer>) {
                     });
                 });
         });
-    commands.insert_resource(ui_assets);
-}
\ No newline at end of file
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::combat::CombatStats;
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StatusKind {
+    Poison,
+    Burn,
+    Stun,
+    Sleep,
+}
+
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns_left: usize,
+}
+
+/// Statuses on a combatant, works the same for the player and enemies
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+/// What the statuses did when their holder's turn started
+#[derive(Default)]
+pub struct TurnStartOutcome {
+    pub damage: isize,
+    pub skip_turn: bool,
+    pub feedback: Vec<&'static str>,
+}
+
+impl StatusKind {
+    pub fn duration(self) -> usize {
+        match self {
+            StatusKind::Poison => 3,
+            StatusKind::Burn => 3,
+            StatusKind::Stun => 1,
+            StatusKind::Sleep => 3,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "PSN",
+            StatusKind::Burn => "BRN",
+            StatusKind::Stun => "STN",
+            StatusKind::Sleep => "SLP",
+        }
+    }
+
+    /// Finishes "Bat got ..." in the battle log
+    pub fn inflicted_text(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "poisoned",
+            StatusKind::Burn => "burned",
+            StatusKind::Stun => "stunned",
+            StatusKind::Sleep => "put to sleep",
+        }
+    }
+
+    fn on_turn_start(self, stats: &CombatStats, outcome: &mut TurnStartOutcome) {
+        match self {
+            StatusKind::Poison => {
+                outcome.damage += std::cmp::max(stats.max_health / 8, 1);
+                outcome.feedback.push("Poisoned");
+            }
+            StatusKind::Burn => {
+                outcome.damage += 2;
+                outcome.feedback.push("Burning");
+            }
+            StatusKind::Stun => {
+                outcome.skip_turn = true;
+                outcome.feedback.push("Stunned");
+            }
+            StatusKind::Sleep => {
+                outcome.skip_turn = true;
+                outcome.feedback.push("Asleep");
+            }
+        }
+    }
+
+    /// Whether the status survives its holder getting hurt
+    fn survives_damage(self) -> bool {
+        !matches!(self, StatusKind::Sleep)
+    }
+}
+
+impl StatusEffects {
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Adds the status, or refreshes its duration if it's already there
+    pub fn inflict(&mut self, kind: StatusKind) {
+        match self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => effect.turns_left = kind.duration(),
+            None => self.effects.push(StatusEffect {
+                kind,
+                turns_left: kind.duration(),
+            }),
+        }
+    }
+
+    pub fn on_turn_start(&self, stats: &CombatStats) -> TurnStartOutcome {
+        let mut outcome = TurnStartOutcome::default();
+        for effect in self.effects.iter() {
+            effect.kind.on_turn_start(stats, &mut outcome);
+        }
+        outcome
+    }
+
+    pub fn on_turn_end(&mut self) {
+        for effect in self.effects.iter_mut() {
+            effect.turns_left = effect.turns_left.saturating_sub(1);
+        }
+        self.effects.retain(|effect| effect.turns_left > 0);
+    }
+
+    pub fn on_damage_taken(&mut self) {
+        self.effects.retain(|effect| effect.kind.survives_damage());
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    /// Short labels of all active statuses, like "PSN BRN"
+    pub fn label(&self) -> String {
+        self.effects
+            .iter()
+            .map(|effect| effect.kind.label())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::map_format::{EncounterSettings, MapData, NpcKind, Tile, WarpTarget};
+
+// Tilesets are told apart by their image, these are the sheets the game loads
+const GROUND_TILESET: &str = "ground_tileset.png";
+const WORLD_OBJECTS_TILESET: &str = "pokemon_tileset.png";
+
+// Upper bits of a gid are the flip flags
+const GID_MASK: u32 = 0x1fff_ffff;
+
+#[derive(Deserialize)]
+struct Property {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Deserialize)]
+struct Tileset {
+    firstgid: u32,
+    #[serde(default)]
+    name: String,
+    // Only set for tilesets kept in their own file
+    source: Option<String>,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Deserialize)]
+struct Object {
+    #[serde(default)]
+    name: String,
+    // Tiled 1.9 called it class, before and after that it's type
+    #[serde(default, rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    // Tile objects are anchored at their bottom left corner
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        name: String,
+        width: usize,
+        height: usize,
+        // A list of gids with CSV encoding, a string with base64
+        #[serde(default)]
+        data: Value,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        objects: Vec<Object>,
+    },
+    #[serde(rename = "group")]
+    Group {
+        layers: Vec<Layer>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    #[serde(rename = "tilewidth")]
+    tile_width: f32,
+    #[serde(rename = "tileheight")]
+    tile_height: f32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    properties: Vec<Property>,
+    layers: Vec<Layer>,
+    tilesets: Vec<Tileset>,
+}
+
+enum Sheet {
+    Ground,
+    WorldObjects,
+}
+
+fn property<'a>(properties: &'a [Property], name: &str) -> Option<&'a Value> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| &property.value)
+}
+
+fn string_property(properties: &[Property], name: &str) -> Option<String> {
+    property(properties, name).and_then(Value::as_str).map(str::to_string)
+}
+
+fn bool_property(properties: &[Property], name: &str) -> bool {
+    property(properties, name).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn number_property(properties: &[Property], name: &str) -> Option<f64> {
+    property(properties, name).and_then(Value::as_f64)
+}
+
+impl TiledMap {
+    /// Which sheet a gid draws from, its index there and the flags set on it in Tiled
+    fn lookup(&self, gid: u32) -> anyhow::Result<(Sheet, usize, bool, bool)> {
+        let tileset = self
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.firstgid <= gid)
+            .max_by_key(|tileset| tileset.firstgid)
+            .with_context(|| format!("No tileset has tile {}", gid))?;
+        let id = gid - tileset.firstgid;
+
+        let sheet = if tileset.image.ends_with(GROUND_TILESET) {
+            Sheet::Ground
+        } else if tileset.image.ends_with(WORLD_OBJECTS_TILESET) {
+            Sheet::WorldObjects
+        } else {
+            bail!(
+                "Tileset '{}' uses {}, only {} and {} can be drawn",
+                tileset.name,
+                tileset.image,
+                GROUND_TILESET,
+                WORLD_OBJECTS_TILESET
+            );
+        };
+        let properties = tileset
+            .tiles
+            .iter()
+            .find(|tile| tile.id == id)
+            .map_or(&[][..], |tile| &tile.properties[..]);
+        Ok((
+            sheet,
+            id as usize,
+            bool_property(properties, "solid"),
+            bool_property(properties, "encounter"),
+        ))
+    }
+
+    /// Tiles an object covers, a point or tile object covers the one it sits on.
+    /// None of them when it starts above or left of the map
+    fn covered_tiles(&self, object: &Object) -> Vec<(usize, usize)> {
+        let top = match object.gid {
+            Some(_) => object.y - self.tile_height,
+            None => object.y,
+        };
+        let left = (object.x / self.tile_width).floor();
+        let top = (top / self.tile_height).floor();
+        if left < 0.0 || top < 0.0 {
+            return Vec::new();
+        }
+        let (left, top) = (left as usize, top as usize);
+        let (right, bottom) = if object.gid.is_none() && object.width > 0.0 && object.height > 0.0 {
+            (
+                ((object.x + object.width) / self.tile_width).ceil() as usize,
+                ((object.y + object.height) / self.tile_height).ceil() as usize,
+            )
+        } else {
+            (left + 1, top + 1)
+        };
+
+        let mut tiles = Vec::new();
+        for y in top..bottom.min(self.height) {
+            for x in left..right.min(self.width) {
+                tiles.push((x, y));
+            }
+        }
+        tiles
+    }
+}
+
+fn flatten_layers(layers: Vec<Layer>, flat: &mut Vec<Layer>) {
+    for layer in layers {
+        match layer {
+            Layer::Group { layers } => flatten_layers(layers, flat),
+            layer => flat.push(layer),
+        }
+    }
+}
+
+/// Reads a map saved from Tiled as JSON (.tmj). Tile layers are drawn bottom to top,
+/// tileset tiles can have `solid` and `encounter` bool properties. Object layers hold
+/// `npc` (`npc` = healer or merchant, with a `shop`), `warp` (`map`, `x`, `y`),
+/// `spawn` and `encounter` objects. The map's own properties give the area `name`,
+/// `encounter_chance`, `encounter_interval`, comma separated `enemies` and `can_run`
+pub fn parse_tiled_map(text: &str) -> anyhow::Result<MapData> {
+    let mut tiled: TiledMap = serde_json::from_str(text).context("Not a Tiled JSON map")?;
+    if tiled.infinite {
+        bail!("Infinite maps aren't supported, turn it off in the map properties");
+    }
+    if tiled.width == 0 || tiled.height == 0 {
+        bail!("The map has no tiles");
+    }
+    if let Some(tileset) = tiled.tilesets.iter().find(|tileset| tileset.source.is_some()) {
+        bail!(
+            "Tileset {} is in its own file, embed it with Map > Embed Tilesets",
+            tileset.source.as_deref().unwrap_or_default()
+        );
+    }
+
+    let defaults = EncounterSettings::default();
+    let encounters = EncounterSettings {
+        chance: number_property(&tiled.properties, "encounter_chance")
+            .map_or(defaults.chance, |chance| chance as f32),
+        interval: number_property(&tiled.properties, "encounter_interval")
+            .map_or(defaults.interval, |interval| interval as f32),
+        enemies: string_property(&tiled.properties, "enemies")
+            .map(|enemies| {
+                enemies
+                    .split(',')
+                    .map(|enemy| enemy.trim().to_string())
+                    .filter(|enemy| !enemy.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        can_run: property(&tiled.properties, "can_run")
+            .and_then(Value::as_bool)
+            .unwrap_or(defaults.can_run),
+    };
+    let empty = Tile {
+        index: 0,
+        solid: false,
+        encounter: false,
+        object: None,
+    };
+    let mut map = MapData {
+        name: string_property(&tiled.properties, "name").context("The map needs a 'name' property")?,
+        encounters: encounters,
+        rows: vec![vec![empty; tiled.width]; tiled.height],
+        npcs: Vec::new(),
+        warps: Vec::new(),
+        spawns: Vec::new(),
+        first_line: 1,
+    };
+
+    let mut layers = Vec::new();
+    flatten_layers(std::mem::take(&mut tiled.layers), &mut layers);
+
+    for layer in layers.iter() {
+        if let Layer::Tiles { name, width, height, data } = layer {
+            let data: Vec<u32> = data
+                .as_array()
+                .and_then(|gids| gids.iter().map(|gid| gid.as_u64().map(|gid| gid as u32)).collect())
+                .with_context(|| format!("Layer '{}' has to be saved with CSV encoding", name))?;
+            if (*width, *height) != (tiled.width, tiled.height) {
+                bail!("Layer '{}' isn't the size of the map", name);
+            }
+            if data.len() != width * height {
+                bail!("Layer '{}' has {} tiles, expected {}", name, data.len(), width * height);
+            }
+            for (i, gid) in data.iter().enumerate() {
+                let gid = gid & GID_MASK;
+                if gid == 0 {
+                    continue;
+                }
+                let (sheet, index, solid, encounter) = tiled.lookup(gid)?;
+                let tile = &mut map.rows[i / *width][i % *width];
+                match sheet {
+                    Sheet::Ground => tile.index = index,
+                    Sheet::WorldObjects => tile.object = Some(index),
+                }
+                tile.solid |= solid;
+                tile.encounter |= encounter;
+            }
+        }
+    }
+
+    for layer in layers.iter() {
+        if let Layer::Objects { objects } = layer {
+            for object in objects.iter() {
+                let tiles = tiled.covered_tiles(object);
+                let (x, y) = match tiles.first() {
+                    Some(&tile) => tile,
+                    None => bail!("Object '{}' is outside the map", object.name),
+                };
+                match object.kind.as_str() {
+                    "npc" => {
+                        let npc = match string_property(&object.properties, "npc").as_deref() {
+                            Some("healer") => NpcKind::Healer,
+                            Some("merchant") => NpcKind::Merchant(
+                                string_property(&object.properties, "shop")
+                                    .with_context(|| format!("Merchant '{}' needs a 'shop'", object.name))?,
+                            ),
+                            other => bail!("NPC '{}' has an unknown npc {:?}", object.name, other),
+                        };
+                        map.npcs.push((x, y, npc));
+                    }
+                    "warp" => {
+                        let target_map = string_property(&object.properties, "map")
+                            .with_context(|| format!("Warp '{}' needs a 'map'", object.name))?;
+                        let target_tile = |name| {
+                            number_property(&object.properties, name)
+                                .map(|value| value as usize)
+                                .with_context(|| format!("Warp '{}' needs '{}'", object.name, name))
+                        };
+                        let target = WarpTarget {
+                            map: target_map,
+                            x: target_tile("x")?,
+                            y: target_tile("y")?,
+                        };
+                        // A wide door is a warp on every tile it covers
+                        for &(x, y) in tiles.iter() {
+                            map.warps.push((x, y, target.clone()));
+                        }
+                    }
+                    "spawn" => map.spawns.push((x, y)),
+                    "encounter" => {
+                        for &(x, y) in tiles.iter() {
+                            map.rows[y][x].encounter = true;
+                        }
+                    }
+                    other => bail!("Object '{}' has unknown type '{}'", object.name, other),
+                }
+            }
+        }
+    }
+    Ok(map)
+}
@@ -1,12 +1,14 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
 };
 
-use bevy::prelude::*;
+use dreadblaze::map_format::{parse_map_file, EncounterSettings, MapData, NpcKind, WarpTarget};
 
 use crate::{
-    GameState, TILE_SIZE, npc::Npc, graphics::{spawn_ground_tile_sprite, GroundTilesSheet, CharacterSheet, spawn_character_sprite, spawn_world_object_sprite, WorldObjectsSheet, FrameAnimation},
+    GameState, TILE_SIZE, npc::Npc, player::Player, graphics::{spawn_ground_tile_sprite, GroundTilesSheet, CharacterSheet, spawn_character_sprite, spawn_world_object_sprite, WorldObjectsSheet, FrameAnimation},
 };
 
 pub struct TileMapPlugin;
@@ -20,81 +22,299 @@ struct Map;
 #[derive(Component)]
 pub struct TileCollider;
 
+/// Door to another map, stepping on it starts the warp
+#[derive(Component)]
+pub struct Warp(pub WarpTarget);
+
+/// Where the player goes once the screen is faded, picked up by the Warp state
+pub struct WarpDestination {
+    pub map: String,
+    pub position: Vec3,
+}
+
+#[derive(Default)]
+pub struct PendingWarp(pub Option<WarpDestination>);
+
+#[derive(TypeUuid)]
+#[uuid = "4f0b8a3e-2d6c-4b91-9e57-a1c3f8d20e64"]
+pub struct MapAsset(pub MapData);
+
+#[derive(Default)]
+pub struct MapAssetLoader;
+
+/// Map file the overworld is built from, relative to the assets folder
+pub struct CurrentMap(pub String);
+
+/// The loaded CurrentMap, the Map entities get rebuilt from it whenever it's dirty
+#[derive(Default)]
+pub struct MapBuild {
+    handle: Handle<MapAsset>,
+    dirty: bool,
+    // New games start on the spawn marker, which is only known once the map is in
+    pub to_spawn: bool,
+}
+
+/// Header of the map that's built right now
+#[derive(Default)]
+pub struct CurrentArea {
+    pub name: String,
+    pub encounters: EncounterSettings,
+    // From the spawn marker, new games start here
+    pub spawn: Vec3,
+}
+
+impl Warp {
+    pub fn destination(&self) -> WarpDestination {
+        WarpDestination {
+            map: self.0.map.clone(),
+            position: tile_position(self.0.x, self.0.y, 900.0),
+        }
+    }
+}
+
+impl Default for CurrentMap {
+    fn default() -> Self {
+        CurrentMap("maps/village.map".to_string())
+    }
+}
+
+impl AssetLoader for MapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let map = parse_map_file(load_context.path(), std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(MapAsset(map)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map", "tmj"]
+    }
+}
+
 impl Plugin for TileMapPlugin {
     fn build(&self, app: &mut App) {
-        app
-            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(create_simple_map))
+        app.add_asset::<MapAsset>()
+            .init_asset_loader::<MapAssetLoader>()
+            .init_resource::<CurrentMap>()
+            .init_resource::<CurrentArea>()
+            .init_resource::<MapBuild>()
+            .init_resource::<PendingWarp>()
+            .add_system(watch_map_changes)
+            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(load_current_map))
+            .add_system_set(SystemSet::on_update(GameState::Overworld).with_system(build_map))
+            .add_system_set(SystemSet::on_enter(GameState::Warp).with_system(warp_to_map))
+            .add_system_set(
+                SystemSet::on_update(GameState::Warp)
+                    .with_system(build_map)
+                    .with_system(finish_warp.after(build_map)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Overworld).with_system(despawn_map))
             .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_map_recursive))
             .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_map_recursive));
     }
 }
 
-fn create_simple_map(
+/// World position of a map tile
+pub fn tile_position(x: usize, y: usize, z: f32) -> Vec3 {
+    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, z)
+}
+
+fn load_current_map(
+    assets: Res<AssetServer>,
+    current_map: Res<CurrentMap>,
+    mut map_build: ResMut<MapBuild>,
+) {
+    map_build.handle = assets.load(current_map.0.as_str());
+    map_build.dirty = true;
+}
+
+// Hot reloading a map file lands here, the rebuild waits for the overworld.
+// A map that failed to load comes in as created once the file is fixed
+fn watch_map_changes(mut events: EventReader<AssetEvent<MapAsset>>, mut map_build: ResMut<MapBuild>) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle }
+                if *handle == map_build.handle =>
+            {
+                map_build.dirty = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Replaces the Map entities once the map is loaded, the player stays where they are
+fn build_map(
     mut commands: Commands,
     ground_tiles: Res<GroundTilesSheet>,
     characters: Res<CharacterSheet>,
-    world_objects: Res<WorldObjectsSheet>
+    world_objects: Res<WorldObjectsSheet>,
+    assets: Res<AssetServer>,
+    maps: Res<Assets<MapAsset>>,
+    current_map: Res<CurrentMap>,
+    mut map_build: ResMut<MapBuild>,
+    mut area: ResMut<CurrentArea>,
+    mut player_query: Query<(&mut Player, &mut Transform)>,
+    map_query: Query<Entity, With<Map>>,
+) {
+    if !map_build.dirty {
+        return;
+    }
+    let map = match maps.get(&map_build.handle) {
+        Some(map) => map,
+        None => {
+            // The loader already logged why, the next edit of the file gets another try
+            if assets.get_load_state(&map_build.handle) == LoadState::Failed {
+                error!("Map {} couldn't be loaded", current_map.0);
+                map_build.dirty = false;
+            }
+            return;
+        }
+    };
+    map_build.dirty = false;
+
+    for entity in map_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_map(&mut commands, &ground_tiles, &characters, &world_objects, &map.0, &mut area);
+
+    if map_build.to_spawn {
+        map_build.to_spawn = false;
+        if let Ok((mut player, mut transform)) = player_query.get_single_mut() {
+            transform.translation = area.spawn;
+            player.respawn_point = area.spawn;
+        }
+    }
+}
+
+fn warp_to_map(
+    assets: Res<AssetServer>,
+    mut current_map: ResMut<CurrentMap>,
+    mut map_build: ResMut<MapBuild>,
+    mut pending_warp: ResMut<PendingWarp>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    let destination = match pending_warp.0.take() {
+        Some(destination) => destination,
+        None => return,
+    };
+    if destination.map != current_map.0 {
+        // Built while the Warp state holds, the fade hides the wait
+        current_map.0 = destination.map;
+        map_build.handle = assets.load(current_map.0.as_str());
+        map_build.dirty = true;
+    }
+    player_query.single_mut().translation = destination.position;
+}
+
+// The overworld only comes back once the new map stands, until then the
+// player would walk into the old map's walls, doors and grass
+fn finish_warp(map_build: Res<MapBuild>, mut state: ResMut<State<GameState>>) {
+    if !map_build.dirty {
+        state.pop().unwrap();
+    }
+}
+
+fn spawn_map(
+    commands: &mut Commands,
+    ground_tiles: &GroundTilesSheet,
+    characters: &CharacterSheet,
+    world_objects: &WorldObjectsSheet,
+    map: &MapData,
+    area: &mut CurrentArea,
 ) {
-    let file = File::open("assets/maps/map.txt").expect("No map file found");
     let mut tiles = Vec::new();
 
-    for (y, line) in BufReader::new(file).lines().enumerate() {
-        if let Ok(line) = line {
-            for (x, char) in line.chars().enumerate() {
-                let index = match char {
-                    '.' => ground_tiles.sand,
-                    '~' => ground_tiles.grass,
-                    '#' => ground_tiles.wall,
-                    _ => ground_tiles.sand
-                };
-
-                let tile = spawn_ground_tile_sprite(
-                    &mut commands,
-                    &ground_tiles,
-                    index,
-                    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, 100.0),
+    for (y, row) in map.rows.iter().enumerate() {
+        for (x, map_tile) in row.iter().enumerate() {
+            let tile = spawn_ground_tile_sprite(
+                commands,
+                ground_tiles,
+                map_tile.index,
+                tile_position(x, y, 100.0),
+                Vec3::splat(1.0),
+            );
+
+            if map_tile.solid {
+                commands.entity(tile).insert(TileCollider);
+            }
+            if let Some(object) = map_tile.object {
+                let world_object_sprite = spawn_world_object_sprite(
+                    commands,
+                    world_objects,
+                    object,
+                    Vec3::new(0.0, 0.0, 150.0),
                     Vec3::splat(1.0),
                 );
-
-                if char == '#' {
-                    commands.entity(tile).insert(TileCollider);
-                }
-                if char == '~' {
-                    let world_object_sprite = spawn_world_object_sprite(
-                        &mut commands,
-                        &world_objects,
-                        world_objects.grass[0],
-                        Vec3::new(0.0, 0.0, 150.0),
-                        Vec3::splat(0.8),
-                    );
-                    commands.entity(world_object_sprite)
-                        .insert(FrameAnimation {
-                            timer: Timer::from_seconds(0.5, true),
-                            frames: world_objects.grass.to_vec(),
-                            current_frame: 0
-                    });
-                    commands.entity(tile)
-                        .insert(Name::new("grass_tile".to_string()))
-                        .insert(EncounterSpawner)
-                        .add_child(world_object_sprite);
-                }
-                if char == '@' {
-                    let character_tile = spawn_character_sprite(
-                        &mut commands,
-                        &characters,
-                        characters.healer,
-                        Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, 150.0),
-                        Vec3::splat(1.0)
-                    );
-                    tiles.push(character_tile);
-                    commands.entity(tile).insert(Npc::Healer).insert(TileCollider);
-                }
-
-                tiles.push(tile);
+                commands.entity(tile).add_child(world_object_sprite);
             }
+            // Encounter tiles without anything drawn on them get the swaying grass
+            if map_tile.encounter && map_tile.object.is_none() {
+                let world_object_sprite = spawn_world_object_sprite(
+                    commands,
+                    world_objects,
+                    world_objects.grass[0],
+                    Vec3::new(0.0, 0.0, 150.0),
+                    Vec3::splat(0.8),
+                );
+                commands.entity(world_object_sprite)
+                    .insert(FrameAnimation {
+                        timer: Timer::from_seconds(0.5, true),
+                        frames: world_objects.grass.to_vec(),
+                        current_frame: 0
+                });
+                commands.entity(tile)
+                    .insert(Name::new("grass_tile".to_string()))
+                    .insert(EncounterSpawner)
+                    .add_child(world_object_sprite);
+            }
+
+            tiles.push(tile);
         }
     }
 
+    for (x, y, npc) in map.npcs.iter() {
+        let (index, npc) = match npc {
+            NpcKind::Healer => (characters.healer, Npc::Healer),
+            NpcKind::Merchant(shop) => (characters.merchant, Npc::Merchant(shop.clone())),
+        };
+        let character_tile = spawn_character_sprite(
+            commands,
+            characters,
+            index,
+            tile_position(*x, *y, 150.0),
+            Vec3::splat(1.0)
+        );
+        commands.entity(character_tile).insert(npc).insert(TileCollider);
+        tiles.push(character_tile);
+    }
+
+    for (x, y, target) in map.warps.iter() {
+        let warp = commands
+            .spawn()
+            .insert(Name::new("Warp"))
+            .insert(Warp(target.clone()))
+            .insert(Transform::from_translation(tile_position(*x, *y, 100.0)))
+            .insert(GlobalTransform::default())
+            .id();
+        tiles.push(warp);
+    }
+
+    if map.spawns.len() != 1 {
+        warn!("{} should have one spawn marker, found {}", map.name, map.spawns.len());
+    }
+    let (spawn_x, spawn_y) = map.spawn().unwrap_or((0, 0));
+    *area = CurrentArea {
+        name: map.name.clone(),
+        encounters: map.encounters.clone(),
+        spawn: tile_position(spawn_x, spawn_y, 900.0),
+    };
+
     commands
         .spawn()
         .insert(Map)
@@ -104,6 +324,12 @@ fn create_simple_map(
         .push_children(&tiles);
 }
 
+fn despawn_map(mut commands: Commands, map_query: Query<Entity, With<Map>>) {
+    for map in map_query.iter() {
+        commands.entity(map).despawn_recursive();
+    }
+}
+
 fn hide_map_recursive(
     entities: Query<Entity, With<Map>>,
     mut visibility_query: Query<&mut Visibility>,